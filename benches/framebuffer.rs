@@ -0,0 +1,128 @@
+#![feature(test)]
+
+extern crate test;
+
+use pachip8risu_playdate::framebuffer::Framebuffer;
+use test::{black_box, Bencher};
+
+const SPRITE: [u8; 15] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0,
+];
+
+/// The one bool per pixel framebuffer it replaced, kept to compare against
+struct BoolFramebuffer {
+    pixels: Box<[bool]>,
+    width: usize,
+    height: usize,
+}
+
+impl BoolFramebuffer {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            pixels: vec![false; 128 * 64].into_boxed_slice(),
+            width,
+            height,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pixels = vec![false; 128 * 64].into_boxed_slice();
+    }
+
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+
+        for (y_offset, row_bits) in sprite.iter().enumerate() {
+            for x_offset in 0..8 {
+                if row_bits & (0x80 >> x_offset) == 0 {
+                    continue;
+                }
+
+                let gfx_x = (x + x_offset) % self.width;
+                let gfx_y = (y + y_offset) % self.height;
+                let index = gfx_x + gfx_y * self.width;
+
+                collision |= self.pixels[index];
+                self.pixels[index] ^= true;
+            }
+        }
+
+        collision
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        self.pixels.rotate_right(self.width * n);
+    }
+}
+
+fn draw_packed(framebuffer: &mut Framebuffer, x: usize, y: usize, sprite: &[u8]) -> bool {
+    let mut collision = false;
+
+    for (y_offset, row_bits) in sprite.iter().enumerate() {
+        collision |= framebuffer.xor_row(x, y + y_offset, u16::from(*row_bits), 8);
+    }
+
+    collision
+}
+
+#[bench]
+fn draw_sprites_bool(b: &mut Bencher) {
+    let mut framebuffer = BoolFramebuffer::new(128, 64);
+
+    b.iter(|| {
+        for i in 0..64 {
+            black_box(framebuffer.draw(i * 3, i, &SPRITE));
+        }
+    });
+}
+
+#[bench]
+fn draw_sprites_packed(b: &mut Bencher) {
+    let mut framebuffer = Framebuffer::new(128, 64);
+
+    b.iter(|| {
+        for i in 0..64 {
+            black_box(draw_packed(&mut framebuffer, i * 3, i, &SPRITE));
+        }
+    });
+}
+
+#[bench]
+fn clear_bool(b: &mut Bencher) {
+    let mut framebuffer = BoolFramebuffer::new(128, 64);
+
+    b.iter(|| {
+        framebuffer.clear();
+        black_box(&framebuffer.pixels);
+    });
+}
+
+#[bench]
+fn clear_packed(b: &mut Bencher) {
+    let mut framebuffer = Framebuffer::new(128, 64);
+
+    b.iter(|| {
+        framebuffer.clear();
+        black_box(&framebuffer);
+    });
+}
+
+#[bench]
+fn scroll_bool(b: &mut Bencher) {
+    let mut framebuffer = BoolFramebuffer::new(128, 64);
+
+    b.iter(|| {
+        framebuffer.scroll_down(4);
+        black_box(&framebuffer.pixels);
+    });
+}
+
+#[bench]
+fn scroll_packed(b: &mut Bencher) {
+    let mut framebuffer = Framebuffer::new(128, 64);
+
+    b.iter(|| {
+        framebuffer.scroll_down(4);
+        black_box(&framebuffer);
+    });
+}
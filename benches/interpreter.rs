@@ -0,0 +1,68 @@
+#![feature(test)]
+
+extern crate test;
+
+use pachip8risu_playdate::database::ROMS;
+use pachip8risu_playdate::pachip8risu::{Chip8, Quirks};
+use test::{black_box, Bencher};
+
+// 10000 instructions per iteration
+const SPEED: u32 = 600_000;
+const FRAME_MICROS: u32 = 16_667;
+
+fn run_rom(b: &mut Bencher, name: &str, cache: bool) {
+    let info = ROMS.iter().find(|info| info.name == name).unwrap();
+
+    let mut cpu = Chip8::new();
+    cpu.set_variant(info.variant);
+    // Nothing should stop the frame early
+    cpu.set_quirks(Quirks::default());
+    // Every instruction has to run, not be fast-forwarded
+    cpu.set_idle_skip(false);
+    cpu.set_instruction_cache(cache);
+    cpu.load_rom(info.rom, None).unwrap();
+    cpu.set_speed(SPEED);
+
+    // A failing ROM would only time the error
+    b.iter(|| black_box(cpu.run_for(FRAME_MICROS)).unwrap());
+}
+
+#[bench]
+fn rockto_decode(b: &mut Bencher) {
+    run_rom(b, "rockto", false);
+}
+
+#[bench]
+fn rockto_cached(b: &mut Bencher) {
+    run_rom(b, "rockto", true);
+}
+
+#[bench]
+fn piper_decode(b: &mut Bencher) {
+    run_rom(b, "piper", false);
+}
+
+#[bench]
+fn piper_cached(b: &mut Bencher) {
+    run_rom(b, "piper", true);
+}
+
+#[bench]
+fn tetris_decode(b: &mut Bencher) {
+    run_rom(b, "tetris", false);
+}
+
+#[bench]
+fn tetris_cached(b: &mut Bencher) {
+    run_rom(b, "tetris", true);
+}
+
+#[bench]
+fn space_invaders_decode(b: &mut Bencher) {
+    run_rom(b, "space-invaders", false);
+}
+
+#[bench]
+fn space_invaders_cached(b: &mut Bencher) {
+    run_rom(b, "space-invaders", true);
+}
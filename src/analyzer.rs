@@ -0,0 +1,538 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::Range;
+
+use crate::pachip8risu::{MemoryMap, Variant, ENTRY_POINT, HI_RES_ENTRY_POINT};
+use OpcodeFamily::{Chip8, Chip8X, MegaChip, SuperChip, XoChip};
+
+/// Group of opcodes that came with one interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OpcodeFamily {
+    /// The instruction set of the VIP interpreter
+    Chip8,
+    /// 0NNN calls into 1802 machine code, which only the VIP can run
+    MachineCode,
+    /// 0230 of the 64x64 interpreter, which clears the screen
+    HiRes,
+    /// 0000, left blank for an instruction the ROM writes there while running
+    SelfModified,
+    Chip8X,
+    SuperChip,
+    XoChip,
+    MegaChip,
+}
+
+// Opcodes as (mask, value) pairs, the first match wins
+const OPCODES: &[(u16, u16, OpcodeFamily)] = &[
+    (0xFFFF, 0x00E0, Chip8),
+    (0xFFFF, 0x00EE, Chip8),
+    (0xFFF0, 0x00C0, SuperChip),
+    (0xFFFF, 0x00FB, SuperChip),
+    (0xFFFF, 0x00FC, SuperChip),
+    (0xFFFF, 0x00FD, SuperChip),
+    (0xFFFF, 0x00FE, SuperChip),
+    (0xFFFF, 0x00FF, SuperChip),
+    (0xFFF0, 0x00D0, XoChip),
+    (0xFFFE, 0x0010, MegaChip),
+    (0xFFFF, 0x02A0, Chip8X),
+    (0xF000, 0x1000, Chip8),
+    (0xF000, 0x2000, Chip8),
+    (0xF000, 0x3000, Chip8),
+    (0xF000, 0x4000, Chip8),
+    (0xF00F, 0x5000, Chip8),
+    (0xF00F, 0x5001, Chip8X),
+    (0xF00F, 0x5002, XoChip),
+    (0xF00F, 0x5003, XoChip),
+    (0xF000, 0x6000, Chip8),
+    (0xF000, 0x7000, Chip8),
+    (0xF008, 0x8000, Chip8),
+    (0xF00F, 0x800E, Chip8),
+    (0xF00F, 0x9000, Chip8),
+    (0xF000, 0xA000, Chip8),
+    (0xF000, 0xB000, Chip8),
+    (0xF000, 0xC000, Chip8),
+    (0xF00F, 0xD000, SuperChip),
+    (0xF000, 0xD000, Chip8),
+    (0xF0FF, 0xE09E, Chip8),
+    (0xF0FF, 0xE0A1, Chip8),
+    (0xF0FF, 0xE0F2, Chip8X),
+    (0xF0FF, 0xE0F5, Chip8X),
+    (0xFFFF, 0xF000, XoChip),
+    (0xFFFF, 0xF002, XoChip),
+    (0xF0FF, 0xF001, XoChip),
+    (0xF0FF, 0xF007, Chip8),
+    (0xF0FF, 0xF00A, Chip8),
+    (0xF0FF, 0xF015, Chip8),
+    (0xF0FF, 0xF018, Chip8),
+    (0xF0FF, 0xF01E, Chip8),
+    (0xF0FF, 0xF029, Chip8),
+    (0xF0FF, 0xF030, SuperChip),
+    (0xF0FF, 0xF033, Chip8),
+    (0xF0FF, 0xF03A, XoChip),
+    (0xF0FF, 0xF055, Chip8),
+    (0xF0FF, 0xF065, Chip8),
+    (0xF0FF, 0xF075, SuperChip),
+    (0xF0FF, 0xF085, SuperChip),
+    (0xF0FF, 0xF0F8, Chip8X),
+    (0xF0FF, 0xF0FB, Chip8X),
+];
+
+// What Mega-Chip adds in the 0NNN range
+const MEGA_OPCODES: &[(u16, u16, OpcodeFamily)] = &[
+    (0xFFF0, 0x00B0, MegaChip),
+    (0xFF00, 0x0100, MegaChip),
+    (0xFF00, 0x0200, MegaChip),
+    (0xFF00, 0x0300, MegaChip),
+    (0xFF00, 0x0400, MegaChip),
+    (0xFF00, 0x0500, MegaChip),
+    (0xFFF0, 0x0600, MegaChip),
+    (0xFFFF, 0x0700, MegaChip),
+    (0xFFF0, 0x0800, MegaChip),
+    (0xFF00, 0x0900, MegaChip),
+];
+
+fn find(opcodes: &[(u16, u16, OpcodeFamily)], opcode: u16) -> Option<OpcodeFamily> {
+    opcodes
+        .iter()
+        .find(|(mask, value, _)| opcode & mask == *value)
+        .map(|&(_, _, family)| family)
+}
+
+/// Where execution can go after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// On to the next instruction
+    Next,
+    Jump(u16),
+    /// To NNN, and back to the next instruction on return
+    Call(u16),
+    /// To the next instruction or the one after
+    Skip,
+    Return,
+    /// BNNN, to NNN plus V0, which can't be known without running the ROM
+    ComputedJump(u16),
+    /// Nowhere, like 00FD or an unknown opcode
+    Stop,
+}
+
+/// An instruction read out of a ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    pub address: usize,
+    pub opcode: u16,
+    /// 4 for the opcodes followed by a 16-bit address, 2 for the rest
+    pub len: usize,
+    /// The 16-bit address after F000 and Mega-Chip 01NN
+    pub operand: Option<u16>,
+    /// `None` for opcodes no interpreter knows
+    pub family: Option<OpcodeFamily>,
+    pub flow: Flow,
+}
+
+impl Decoded {
+    /// Whether the core can run it yet
+    #[must_use]
+    pub const fn is_supported(&self) -> bool {
+        match self.family {
+            // Only the scroll up is in so far
+            Some(OpcodeFamily::XoChip) => self.opcode & 0xFFF0 == 0x00D0,
+            // FX75 and FX85 keep flags in RPL
+            Some(OpcodeFamily::SuperChip) => !matches!(self.opcode & 0xF0FF, 0xF075 | 0xF085),
+            // The core stops on 0000, it only runs once the ROM has written over it
+            Some(OpcodeFamily::MachineCode | OpcodeFamily::SelfModified) | None => false,
+            Some(_) => true,
+        }
+    }
+}
+
+/// Disassembles it, with the mnemonics of Cowgod's reference and the extensions named after the
+/// core's instructions
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opcode = self.opcode;
+        let x = (opcode >> 8) & 0xF;
+        let y = (opcode >> 4) & 0xF;
+        let n = opcode & 0xF;
+        let kk = opcode & 0xFF;
+        let nnn = opcode & 0xFFF;
+        let operand = self.operand.unwrap_or_default();
+
+        if self.family == Some(OpcodeFamily::MegaChip) {
+            return match (x, y) {
+                (0, 1) if n == 0 => write!(f, "MEGAOFF"),
+                (0, 1) => write!(f, "MEGAON"),
+                (0, _) => write!(f, "SCU {n}"),
+                (1, _) => write!(f, "LDHI {:#08X}", (u32::from(kk) << 16) | u32::from(operand)),
+                (2, _) => write!(f, "LDPAL {kk}"),
+                (3, _) => write!(f, "SPRW {kk}"),
+                (4, _) => write!(f, "SPRH {kk}"),
+                (5, _) => write!(f, "ALPHA {kk:#04X}"),
+                (6, _) => write!(f, "DIGISND {n}"),
+                (7, _) => write!(f, "STOPSND"),
+                (8, _) => write!(f, "BMODE {n}"),
+                _ => write!(f, "CCOL {kk:#04X}"),
+            };
+        }
+
+        match self.family {
+            Some(OpcodeFamily::HiRes) => return write!(f, "CLS"),
+            Some(OpcodeFamily::MachineCode) => return write!(f, "SYS {nnn:#05X}"),
+            Some(OpcodeFamily::SelfModified) | None => return write!(f, "DW {opcode:#06X}"),
+            _ => {}
+        }
+
+        match (opcode >> 12, n) {
+            (0, _) => match kk {
+                0xE0 => write!(f, "CLS"),
+                0xEE => write!(f, "RET"),
+                0xA0 => write!(f, "BACKGROUND"),
+                0xFB => write!(f, "SCR"),
+                0xFC => write!(f, "SCL"),
+                0xFD => write!(f, "EXIT"),
+                0xFE => write!(f, "LOW"),
+                0xFF => write!(f, "HIGH"),
+                _ if y == 0xC => write!(f, "SCD {n}"),
+                _ => write!(f, "SCU {n}"),
+            },
+            (0x1, _) => write!(f, "JP {nnn:#05X}"),
+            (0x2, _) => write!(f, "CALL {nnn:#05X}"),
+            (0x3, _) => write!(f, "SE V{x:X}, {kk:#04X}"),
+            (0x4, _) => write!(f, "SNE V{x:X}, {kk:#04X}"),
+            (0x5, 0) => write!(f, "SE V{x:X}, V{y:X}"),
+            (0x5, 1) => write!(f, "ADD V{x:X}, V{y:X}"),
+            (0x5, 2) => write!(f, "SAVE V{x:X}-V{y:X}"),
+            (0x5, _) => write!(f, "LOAD V{x:X}-V{y:X}"),
+            (0x6, _) => write!(f, "LD V{x:X}, {kk:#04X}"),
+            (0x7, _) => write!(f, "ADD V{x:X}, {kk:#04X}"),
+            (0x8, _) => {
+                let mnemonic = ["LD", "OR", "AND", "XOR", "ADD", "SUB", "SHR", "SUBN"]
+                    .get(usize::from(n))
+                    .unwrap_or(&"SHL");
+                write!(f, "{mnemonic} V{x:X}, V{y:X}")
+            }
+            (0x9, _) => write!(f, "SNE V{x:X}, V{y:X}"),
+            (0xA, _) => write!(f, "LD I, {nnn:#05X}"),
+            (0xB, 0) if self.family == Some(OpcodeFamily::Chip8X) => {
+                write!(f, "COLOR V{x:X}, V{y:X}")
+            }
+            (0xB, _) if self.family == Some(OpcodeFamily::Chip8X) => {
+                write!(f, "COLOR V{x:X}, V{y:X}, {n}")
+            }
+            (0xB, _) => write!(f, "JP V0, {nnn:#05X}"),
+            (0xC, _) => write!(f, "RND V{x:X}, {kk:#04X}"),
+            (0xD, _) => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            (0xE, _) => match kk {
+                0x9E => write!(f, "SKP V{x:X}"),
+                0xA1 => write!(f, "SKNP V{x:X}"),
+                0xF2 => write!(f, "SKP2 V{x:X}"),
+                _ => write!(f, "SKNP2 V{x:X}"),
+            },
+            _ => match kk {
+                0x00 => write!(f, "LD I, {operand:#06X}"),
+                0x01 => write!(f, "PLANE {x}"),
+                0x02 => write!(f, "AUDIO"),
+                0x07 => write!(f, "LD V{x:X}, DT"),
+                0x0A => write!(f, "LD V{x:X}, K"),
+                0x15 => write!(f, "LD DT, V{x:X}"),
+                0x18 => write!(f, "LD ST, V{x:X}"),
+                0x1E => write!(f, "ADD I, V{x:X}"),
+                0x29 => write!(f, "LD F, V{x:X}"),
+                0x30 => write!(f, "LD HF, V{x:X}"),
+                0x33 => write!(f, "LD B, V{x:X}"),
+                0x3A => write!(f, "PITCH V{x:X}"),
+                0x55 => write!(f, "LD [I], V{x:X}"),
+                0x65 => write!(f, "LD V{x:X}, [I]"),
+                0x75 => write!(f, "LD R, V{x:X}"),
+                0x85 => write!(f, "LD V{x:X}, R"),
+                0xF8 => write!(f, "OUT V{x:X}"),
+                _ => write!(f, "IN V{x:X}"),
+            },
+        }
+    }
+}
+
+/// Reads instructions out of a ROM loaded at `entry_point`
+#[derive(Debug, Clone, Copy)]
+pub struct Decoder<'a> {
+    rom: &'a [u8],
+    entry_point: usize,
+    /// 0NNN opcodes are Mega-Chip ones instead of machine code
+    mega: bool,
+    hi_res: bool,
+    /// Loaded where CHIP-8X ROMs are, so BXYN colors the screen instead of jumping
+    chip8x: bool,
+}
+
+impl<'a> Decoder<'a> {
+    #[must_use]
+    pub fn new(rom: &'a [u8], entry_point: usize, mega: bool) -> Self {
+        Self {
+            rom,
+            entry_point,
+            mega,
+            hi_res: Variant::Chip8.detect(rom) == Variant::Chip8HiRes,
+            chip8x: entry_point == MemoryMap::for_variant(Variant::Chip8X).entry_point,
+        }
+    }
+
+    /// The instruction at `address`, or `None` if it's not all inside the ROM
+    #[must_use]
+    pub fn decode(&self, address: usize) -> Option<Decoded> {
+        let opcode = self.word(address)?;
+        let family = self.family(opcode);
+
+        let operand = if opcode == 0xF000 || (self.mega && opcode & 0xFF00 == 0x0100) {
+            Some(self.word(address + 2)?)
+        } else {
+            None
+        };
+
+        Some(Decoded {
+            address,
+            opcode,
+            len: if operand.is_some() { 4 } else { 2 },
+            operand,
+            family,
+            flow: self.flow(address, opcode, family),
+        })
+    }
+
+    fn word(&self, address: usize) -> Option<u16> {
+        let offset = address.checked_sub(self.entry_point)?;
+        let bytes = self.rom.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn family(&self, opcode: u16) -> Option<OpcodeFamily> {
+        if self.mega && find(MEGA_OPCODES, opcode).is_some() {
+            return Some(OpcodeFamily::MegaChip);
+        }
+        if self.hi_res && opcode == 0x0230 {
+            return Some(OpcodeFamily::HiRes);
+        }
+        if self.chip8x && opcode & 0xF000 == 0xB000 {
+            return Some(OpcodeFamily::Chip8X);
+        }
+
+        if opcode == 0x0000 {
+            return Some(OpcodeFamily::SelfModified);
+        }
+
+        find(OPCODES, opcode)
+            .or_else(|| (opcode & 0xF000 == 0).then_some(OpcodeFamily::MachineCode))
+    }
+
+    fn flow(&self, address: usize, opcode: u16, family: Option<OpcodeFamily>) -> Flow {
+        if family.is_none() {
+            return Flow::Stop;
+        }
+
+        let nnn = opcode & 0x0FFF;
+        let n = opcode & 0x000F;
+
+        match opcode >> 12 {
+            0 if opcode == 0x00EE => Flow::Return,
+            0 if opcode == 0x00FD => Flow::Stop,
+            // The hi-res interpreter patch runs and comes back further on
+            0x1 if self.hi_res && address == self.entry_point => Flow::Jump(HI_RES_ENTRY_POINT),
+            0x1 => Flow::Jump(nnn),
+            0x2 => Flow::Call(nnn),
+            0x3 | 0x4 | 0xE => Flow::Skip,
+            0x5 | 0x9 if n == 0 => Flow::Skip,
+            0xB if self.chip8x => Flow::Next,
+            0xB => Flow::ComputedJump(nnn),
+            _ => Flow::Next,
+        }
+    }
+}
+
+/// What a ROM does, found without running it
+///
+/// Code is found by following every jump, call and skip from the entry point, everything it can't
+/// reach counts as data. BNNN jump tables can't be followed, so code only they reach shows up as
+/// data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    entry_point: usize,
+    instructions: BTreeMap<usize, Decoded>,
+    code: Vec<bool>,
+    families: BTreeSet<OpcodeFamily>,
+}
+
+impl Analysis {
+    /// Where the ROM was loaded and started from
+    #[must_use]
+    pub const fn entry_point(&self) -> usize {
+        self.entry_point
+    }
+
+    /// Reachable instructions, by address
+    pub fn instructions(&self) -> impl Iterator<Item = &Decoded> {
+        self.instructions.values()
+    }
+
+    #[must_use]
+    pub fn instruction(&self, address: usize) -> Option<&Decoded> {
+        self.instructions.get(&address)
+    }
+
+    /// Whether the byte at `address` is part of a reachable instruction
+    #[must_use]
+    pub fn is_code(&self, address: usize) -> bool {
+        address
+            .checked_sub(self.entry_point)
+            .and_then(|offset| self.code.get(offset))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Address ranges of the ROM that no instruction reaches
+    #[must_use]
+    pub fn data(&self) -> Vec<Range<usize>> {
+        let mut address = self.entry_point;
+
+        self.code
+            .chunk_by(|a, b| a == b)
+            .filter_map(|run| {
+                let range = address..address + run.len();
+                address = range.end;
+                (!run[0]).then_some(range)
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub const fn families(&self) -> &BTreeSet<OpcodeFamily> {
+        &self.families
+    }
+
+    #[must_use]
+    pub fn uses(&self, family: OpcodeFamily) -> bool {
+        self.families.contains(&family)
+    }
+
+    /// Reachable instructions the core can't run yet
+    pub fn unsupported(&self) -> impl Iterator<Item = &Decoded> {
+        self.instructions()
+            .filter(|instruction| !instruction.is_supported())
+    }
+
+    /// BNNN instructions, whose targets weren't followed
+    pub fn computed_jumps(&self) -> impl Iterator<Item = &Decoded> {
+        self.instructions()
+            .filter(|instruction| matches!(instruction.flow, Flow::ComputedJump(_)))
+    }
+
+    /// Jumps and calls out of the ROM, and opcodes no interpreter knows
+    ///
+    /// A walk from the entry point the ROM was written for runs into few of them.
+    #[must_use]
+    pub fn misfits(&self) -> usize {
+        self.instructions()
+            .filter(|instruction| match instruction.flow {
+                Flow::Jump(target) | Flow::Call(target) => !self.is_code(usize::from(target)),
+                _ => instruction.family.is_none(),
+            })
+            .count()
+    }
+
+    /// The most basic variant that has every opcode the ROM uses, and loads it at the entry point
+    #[must_use]
+    pub fn recommended_variant(&self) -> Variant {
+        if self.entry_point == MemoryMap::for_variant(Variant::Chip8X).entry_point {
+            Variant::Chip8X
+        } else if self.uses(OpcodeFamily::MegaChip) {
+            Variant::MegaChip
+        } else if self.uses(OpcodeFamily::XoChip) {
+            Variant::XoChip
+        } else if self.uses(OpcodeFamily::SuperChip) {
+            Variant::SuperChip
+        } else if self.uses(OpcodeFamily::Chip8X) {
+            Variant::Chip8X
+        } else if self.uses(OpcodeFamily::HiRes) {
+            Variant::Chip8HiRes
+        } else {
+            Variant::Chip8
+        }
+    }
+}
+
+/// Finds the code of a ROM loaded at `entry_point`, and the opcodes it uses
+///
+/// CHIP-8X ROMs start at 0x300, see `MemoryMap::for_variant`, and from there BXYN is read as the
+/// CHIP-8X color opcode.
+#[must_use]
+pub fn analyze(rom: &[u8], entry_point: usize) -> Analysis {
+    let analysis = walk(Decoder::new(rom, entry_point, false), rom.len());
+
+    // Mega-Chip reads the 0NNN opcodes differently once it's turned on
+    if analysis.uses(OpcodeFamily::MegaChip) {
+        walk(Decoder::new(rom, entry_point, true), rom.len())
+    } else {
+        analysis
+    }
+}
+
+/// Finds the code of a ROM written for any variant, from the entry point that fits it best
+///
+/// CHIP-8X ROMs start at 0x300 and the others at 0x200. From the wrong one, a ROM jumps out of
+/// itself or into data, so the walk with fewer `Analysis::misfits` wins. On a tie, 0x300 only wins
+/// if the ROM uses CHIP-8X opcodes from there.
+#[must_use]
+pub fn analyze_unknown(rom: &[u8]) -> Analysis {
+    let chip8 = analyze(rom, ENTRY_POINT);
+    let chip8x = analyze(rom, MemoryMap::for_variant(Variant::Chip8X).entry_point);
+
+    match chip8x.misfits().cmp(&chip8.misfits()) {
+        Ordering::Less => chip8x,
+        Ordering::Equal if chip8x.uses(OpcodeFamily::Chip8X) => chip8x,
+        _ => chip8,
+    }
+}
+
+fn walk(decoder: Decoder, len: usize) -> Analysis {
+    let mut analysis = Analysis {
+        entry_point: decoder.entry_point,
+        instructions: BTreeMap::new(),
+        code: vec![false; len],
+        families: BTreeSet::new(),
+    };
+
+    let mut pending = vec![decoder.entry_point];
+
+    while let Some(address) = pending.pop() {
+        if analysis.instructions.contains_key(&address) {
+            continue;
+        }
+        let Some(instruction) = decoder.decode(address) else {
+            continue;
+        };
+
+        analysis.instructions.insert(address, instruction);
+        let offset = address - decoder.entry_point;
+        analysis.code[offset..offset + instruction.len].fill(true);
+        if let Some(family) = instruction.family {
+            analysis.families.insert(family);
+        }
+
+        let next = address + instruction.len;
+        match instruction.flow {
+            Flow::Next => pending.push(next),
+            Flow::Jump(target) => pending.push(usize::from(target)),
+            Flow::Call(target) => pending.extend([next, usize::from(target)]),
+            Flow::Skip => {
+                // XO-Chip skips the whole of F000 NNNN
+                let skipped = decoder
+                    .decode(next)
+                    .map_or(2, |instruction| instruction.len);
+                pending.extend([next, next + skipped]);
+            }
+            Flow::Return | Flow::ComputedJump(_) | Flow::Stop => {}
+        }
+    }
+
+    analysis
+}
@@ -0,0 +1,114 @@
+//! Desktop tools for working on ROMs
+//!
+//! - `pachip8risu cfg <rom> [out.dot]` writes the control flow graph of a ROM as Graphviz source
+//! - `pachip8risu trace <rom> <frames> [out.txt]` writes a line per instruction run, in the
+//!   format of `TraceEntry`
+
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use pachip8risu_playdate::analyzer::analyze_unknown;
+use pachip8risu_playdate::cfg::rom_to_dot;
+use pachip8risu_playdate::pachip8risu::{Chip8, MemoryMap, Variant};
+use pachip8risu_playdate::trace::TraceWriter;
+
+const USAGE: &str = "usage: pachip8risu cfg <rom> [out.dot]\n       \
+                     pachip8risu trace <rom> <frames> [out.txt]";
+
+const FRAME_MICROS: u32 = 16_667;
+
+/// Lets a trace stream into a file
+struct Output(Box<dyn Write>);
+
+impl fmt::Write for Output {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// The variant for the opcodes a ROM uses, and the entry point it was written for
+fn detect_variant(rom: &[u8]) -> Variant {
+    analyze_unknown(rom).recommended_variant()
+}
+
+fn cfg(args: &[String]) -> Result<(), String> {
+    let [rom, rest @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let rom = fs::read(rom).map_err(|error| format!("{rom}: {error}"))?;
+    let variant = detect_variant(&rom);
+    let dot = rom_to_dot(&rom, MemoryMap::for_variant(variant).entry_point);
+
+    match rest {
+        [] => print!("{dot}"),
+        [out] => fs::write(out, dot).map_err(|error| format!("{out}: {error}"))?,
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn trace(args: &[String]) -> Result<(), String> {
+    let (rom, frames, out) = match args {
+        [rom, frames] => (rom, frames, None),
+        [rom, frames, out] => (rom, frames, Some(out)),
+        _ => return Err(USAGE.into()),
+    };
+    let rom = fs::read(rom).map_err(|error| format!("{rom}: {error}"))?;
+    let frames: u32 = frames.parse().map_err(|_| USAGE.to_string())?;
+
+    let out: Box<dyn Write> = match out {
+        Some(out) => Box::new(File::create(out).map_err(|error| format!("{out}: {error}"))?),
+        None => Box::new(io::stdout()),
+    };
+    let writer = Rc::new(RefCell::new(TraceWriter::new(Output(Box::new(
+        BufWriter::new(out),
+    )))));
+
+    let variant = detect_variant(&rom);
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(variant));
+    cpu.set_variant(variant);
+    // Every instruction has to show up in the trace
+    cpu.set_idle_skip(false);
+    cpu.load_rom(&rom, None)
+        .map_err(|error| error.to_string())?;
+    cpu.set_tracer(Some(writer.clone()));
+
+    // Keep the trace up to the instruction that failed
+    let result = (0..frames).try_for_each(|_| cpu.run_for(FRAME_MICROS));
+
+    let mut writer = writer.borrow_mut();
+    writer
+        .result()
+        .map_err(|_| "Couldn't write the trace".to_string())?;
+    writer
+        .get_mut()
+        .0
+        .flush()
+        .map_err(|error| error.to_string())?;
+
+    result.map_err(|error| error.to_string())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.split_first() {
+        Some((command, rest)) if command == "cfg" => cfg(rest),
+        Some((command, rest)) if command == "trace" => trace(rest),
+        _ => Err(USAGE.into()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::analyzer::{analyze, Analysis, Decoded, Flow};
+
+/// Why execution can go from one block to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Falling through, or the skip not taken
+    Next,
+    Jump,
+    Call,
+    /// From the end of a subroutine back to after a call to it
+    Return,
+    Skip,
+    /// BNNN, to NNN plus V0
+    Unresolved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    /// First address of the block it goes to, `None` for BNNN
+    pub to: Option<usize>,
+    pub kind: EdgeKind,
+}
+
+/// Instructions that always run one after the other, from a single way in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub instructions: Vec<Decoded>,
+}
+
+impl BasicBlock {
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.instructions[0].address
+    }
+
+    fn last(&self) -> &Decoded {
+        &self.instructions[self.instructions.len() - 1]
+    }
+}
+
+/// The reachable code of a ROM as basic blocks and the edges between them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub entry_point: usize,
+    /// By first address
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    #[must_use]
+    pub fn new(analysis: &Analysis) -> Self {
+        let leaders = leaders(analysis);
+        let mut blocks = BTreeMap::new();
+
+        for &start in &leaders {
+            let mut instructions = vec![];
+            let mut address = start;
+
+            while let Some(&instruction) = analysis.instruction(address) {
+                instructions.push(instruction);
+                address += instruction.len;
+
+                if instruction.flow != Flow::Next || leaders.contains(&address) {
+                    break;
+                }
+            }
+
+            if !instructions.is_empty() {
+                blocks.insert(start, BasicBlock { instructions });
+            }
+        }
+
+        let mut graph = Self {
+            entry_point: analysis.entry_point(),
+            blocks,
+            edges: vec![],
+        };
+        graph.add_edges(analysis);
+        graph.add_returns();
+        graph
+    }
+
+    fn add_edges(&mut self, analysis: &Analysis) {
+        for block in self.blocks.values() {
+            let from = block.start();
+            let last = block.last();
+            let next = last.address + last.len;
+
+            let mut edge = |to: usize, kind| {
+                if self.blocks.contains_key(&to) {
+                    self.edges.push(Edge {
+                        from,
+                        to: Some(to),
+                        kind,
+                    });
+                }
+            };
+
+            match last.flow {
+                Flow::Next => edge(next, EdgeKind::Next),
+                Flow::Jump(target) => edge(usize::from(target), EdgeKind::Jump),
+                Flow::Call(target) => {
+                    edge(usize::from(target), EdgeKind::Call);
+                    edge(next, EdgeKind::Next);
+                }
+                Flow::Skip => {
+                    let skipped = analysis.instruction(next).map_or(2, |skipped| skipped.len);
+                    edge(next, EdgeKind::Next);
+                    edge(next + skipped, EdgeKind::Skip);
+                }
+                Flow::ComputedJump(_) => self.edges.push(Edge {
+                    from,
+                    to: None,
+                    kind: EdgeKind::Unresolved,
+                }),
+                Flow::Return | Flow::Stop => {}
+            }
+        }
+    }
+
+    /// Links the returns of each subroutine to the instructions after the calls to it
+    fn add_returns(&mut self) {
+        let mut returns = vec![];
+
+        for call in self.edges.iter().filter(|edge| edge.kind == EdgeKind::Call) {
+            let Some(subroutine) = call.to else {
+                continue;
+            };
+            let caller = &self.blocks[&call.from];
+            let back = caller.last().address + caller.last().len;
+
+            for block in self.body(subroutine) {
+                if self.blocks[&block].last().flow == Flow::Return
+                    && self.blocks.contains_key(&back)
+                {
+                    returns.push(Edge {
+                        from: block,
+                        to: Some(back),
+                        kind: EdgeKind::Return,
+                    });
+                }
+            }
+        }
+
+        self.edges.extend(returns);
+    }
+
+    /// Blocks reachable from `start` without going into calls
+    fn body(&self, start: usize) -> BTreeSet<usize> {
+        let mut body = BTreeSet::new();
+        let mut pending = vec![start];
+
+        while let Some(block) = pending.pop() {
+            if !body.insert(block) {
+                continue;
+            }
+
+            let inside = self.edges.iter().filter(|edge| {
+                edge.from == block
+                    && matches!(edge.kind, EdgeKind::Next | EdgeKind::Jump | EdgeKind::Skip)
+            });
+            pending.extend(inside.filter_map(|edge| edge.to));
+        }
+
+        body
+    }
+
+    /// Graphviz source, one box of disassembly per block
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph rom {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (start, block) in &self.blocks {
+            let mut label = String::new();
+            for instruction in &block.instructions {
+                let _ = write!(label, "{:#05X}  {instruction}\\l", instruction.address);
+            }
+
+            let entry = if *start == self.entry_point {
+                ", penwidth=2"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot, "    b{start:X} [label=\"{label}\"{entry}];");
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", color=blue]",
+                EdgeKind::Return => " [label=\"return\", style=dashed, color=blue]",
+                EdgeKind::Skip => " [label=\"skip\"]",
+                EdgeKind::Unresolved => " [label=\"V0 + NNN\", style=dashed, color=red]",
+            };
+
+            if let Some(to) = edge.to {
+                let _ = writeln!(dot, "    b{:X} -> b{to:X}{style};", edge.from);
+            } else {
+                let from = edge.from;
+                let _ = writeln!(
+                    dot,
+                    "    u{from:X} [label=\"?\", shape=diamond, color=red];"
+                );
+                let _ = writeln!(dot, "    b{from:X} -> u{from:X}{style};");
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// First addresses of the blocks, where something other than the previous instruction leads
+fn leaders(analysis: &Analysis) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::from([analysis.entry_point()]);
+
+    for instruction in analysis.instructions() {
+        let next = instruction.address + instruction.len;
+
+        match instruction.flow {
+            Flow::Next => {}
+            Flow::Jump(target) => {
+                leaders.insert(usize::from(target));
+            }
+            Flow::Call(target) => {
+                leaders.extend([usize::from(target), next]);
+            }
+            Flow::Skip => {
+                let skipped = analysis.instruction(next).map_or(2, |skipped| skipped.len);
+                leaders.extend([next, next + skipped]);
+            }
+            Flow::Return | Flow::ComputedJump(_) | Flow::Stop => {
+                leaders.insert(next);
+            }
+        }
+    }
+
+    leaders
+}
+
+/// Graphviz source of the control flow graph of a ROM loaded at `entry_point`
+#[must_use]
+pub fn rom_to_dot(rom: &[u8], entry_point: usize) -> String {
+    ControlFlowGraph::new(&analyze(rom, entry_point)).to_dot()
+}
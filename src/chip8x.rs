@@ -0,0 +1,71 @@
+/// Colors of the VP-590 color board, as RGB
+pub const CHIP8X_COLORS: [u32; 8] = [
+    0x00_0000, // Black
+    0xFF_0000, // Red
+    0x00_00FF, // Blue
+    0xFF_00FF, // Violet
+    0x00_FF00, // Green
+    0xFF_FF00, // Yellow
+    0x00_FFFF, // Aqua
+    0xFF_FFFF, // White
+];
+
+// 02A0 steps through these, blue first
+const BACKGROUNDS: [u8; 4] = [2, 0, 4, 1];
+
+const ZONE_COLUMNS: usize = 8;
+const ZONE_ROWS: usize = 32;
+
+/// Foreground colors of a CHIP-8X screen, kept beside the 1-bit framebuffer
+///
+/// The screen is split into zones 8 pixels wide. BXYN colors them one row at a time and BXY0 four
+/// rows at a time, so the map keeps one color per 8x1 zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorMap {
+    zones: [[u8; ZONE_COLUMNS]; ZONE_ROWS],
+    background: usize,
+}
+
+impl ColorMap {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            // Red on blue, like the interpreter starts
+            zones: [[1; ZONE_COLUMNS]; ZONE_ROWS],
+            background: 0,
+        }
+    }
+
+    /// Color of the pixel at (x, y) when it's lit
+    #[inline]
+    #[must_use]
+    pub const fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.zones[y % ZONE_ROWS][(x / 8) % ZONE_COLUMNS]
+    }
+
+    /// Color of unlit pixels
+    #[inline]
+    #[must_use]
+    pub const fn background(&self) -> u8 {
+        BACKGROUNDS[self.background]
+    }
+
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    /// Colors `width` by `height` zones of 8x1 pixels from zone (x, y), wrapping at the edges
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u8) {
+        for row in y..y + height {
+            for column in x..x + width {
+                self.zones[row % ZONE_ROWS][column % ZONE_COLUMNS] = color & 0x07;
+            }
+        }
+    }
+}
+
+impl Default for ColorMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
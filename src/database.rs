@@ -0,0 +1,148 @@
+use super::*;
+
+/// Settings for a bundled ROM
+pub struct RomInfo {
+    pub name: &'static str,
+    pub rom: &'static [u8],
+    pub variant: Variant,
+    pub font: FontSet,
+    /// Instructions per second
+    pub speed: u32,
+    pub timing: Timing,
+    pub key_map: KeyMap,
+    /// Crank drives two keys like a paddle
+    pub paddle: Option<Paddle>,
+    /// Accelerometer presses keys too
+    pub tilt: Option<Tilt>,
+    /// Emulation stops while the keypad overlay is open and no key is pressed
+    pub keypad_pauses: bool,
+}
+
+impl RomInfo {
+    #[must_use]
+    pub const fn new(name: &'static str, rom: &'static [u8]) -> Self {
+        Self {
+            name,
+            rom,
+            variant: Variant::SuperChip,
+            font: FontSet::Octo,
+            speed: 6000,
+            timing: Timing::Fixed,
+            key_map: KeyMap::octo(),
+            paddle: None,
+            tilt: None,
+            keypad_pauses: false,
+        }
+    }
+
+    /// Classic VIP games run much slower than the Octo ones
+    #[must_use]
+    pub const fn classic(mut self) -> Self {
+        self.variant = Variant::Chip8;
+        self.font = FontSet::Vip;
+        self.speed = 600;
+        self.timing = Timing::Vip;
+        self
+    }
+
+    #[must_use]
+    pub const fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    #[must_use]
+    pub const fn font(mut self, font: FontSet) -> Self {
+        self.font = font;
+        self
+    }
+
+    #[must_use]
+    pub const fn key_map(mut self, key_map: KeyMap) -> Self {
+        self.key_map = key_map;
+        self
+    }
+
+    #[must_use]
+    pub const fn paddle(mut self, paddle: Paddle) -> Self {
+        self.paddle = Some(paddle);
+        self
+    }
+
+    #[must_use]
+    pub const fn tilt(mut self, tilt: Tilt) -> Self {
+        self.tilt = Some(tilt);
+        self
+    }
+
+    #[must_use]
+    pub const fn keypad_pauses(mut self) -> Self {
+        self.keypad_pauses = true;
+        self
+    }
+}
+
+/// Left and right on 4 and 6, used by most of the classic games
+const LEFT_RIGHT_4_6: KeyMap = KeyMap::empty()
+    .with(Input::Left, 0x4)
+    .with(Input::Right, 0x6);
+
+pub static ROMS: &[RomInfo] = &[
+    RomInfo::new("1dcell", include_bytes!("../roms/1dcell.ch8")),
+    RomInfo::new("binding", include_bytes!("../roms/binding.ch8")),
+    RomInfo::new("breakout", include_bytes!("../roms/breakout.ch8"))
+        .classic()
+        .key_map(LEFT_RIGHT_4_6)
+        .paddle(Paddle::new(0x4, 0x6)),
+    RomInfo::new("car", include_bytes!("../roms/car.ch8")).paddle(Paddle::new(0x7, 0x8)),
+    RomInfo::new("chip8-test-rom", include_bytes!("../roms/chip8-test-rom.ch8")).classic(),
+    RomInfo::new("corax+", include_bytes!("../roms/corax+.ch8")).classic(),
+    RomInfo::new("dodge", include_bytes!("../roms/dodge.ch8")).tilt(Tilt::new().with_x(0x7, 0x9)),
+    RomInfo::new("dvn8", include_bytes!("../roms/dvn8.ch8")),
+    RomInfo::new("eaty", include_bytes!("../roms/eaty.ch8")),
+    RomInfo::new("iq-132", include_bytes!("../roms/iq-132.ch8"))
+        .classic()
+        .keypad_pauses(),
+    RomInfo::new("maze", include_bytes!("../roms/maze.ch8")).classic(),
+    RomInfo::new("minimal-16x16", include_bytes!("../roms/minimal-16x16.ch8")),
+    RomInfo::new("mondrian", include_bytes!("../roms/mondrian.ch8")),
+    RomInfo::new("octogon", include_bytes!("../roms/octogon.ch8")),
+    RomInfo::new("octopeg", include_bytes!("../roms/octopeg.ch8")),
+    RomInfo::new("piper", include_bytes!("../roms/piper.ch8")),
+    RomInfo::new("rockto", include_bytes!("../roms/rockto.ch8")),
+    RomInfo::new("schip-test", include_bytes!("../roms/schip-test.ch8")),
+    RomInfo::new("scrolling", include_bytes!("../roms/scrolling.ch8")),
+    RomInfo::new("sierpinski", include_bytes!("../roms/sierpinski.ch8")).classic(),
+    RomInfo::new("snake", include_bytes!("../roms/snake.ch8")),
+    RomInfo::new("space-invaders", include_bytes!("../roms/space-invaders.ch8"))
+        .classic()
+        .key_map(
+            LEFT_RIGHT_4_6
+                .with(Input::Up, 0x5)
+                .with(Input::A, 0x5)
+                .with(Input::B, 0x5),
+        ),
+    RomInfo::new("sqrt-test", include_bytes!("../roms/sqrt-test.ch8")).classic(),
+    RomInfo::new("superneatboy", include_bytes!("../roms/superneatboy.ch8"))
+        .variant(Variant::XoChip),
+    RomInfo::new("supersquare", include_bytes!("../roms/supersquare.ch8")),
+    RomInfo::new("sweetcopter", include_bytes!("../roms/sweetcopter.ch8"))
+        .tilt(Tilt::new().with_y(0x6, 0x8)),
+    RomInfo::new("tetris", include_bytes!("../roms/tetris.ch8"))
+        .classic()
+        .key_map(
+            KeyMap::empty()
+                .with(Input::Up, 0x4)
+                .with(Input::A, 0x4)
+                .with(Input::Left, 0x5)
+                .with(Input::Right, 0x6)
+                .with(Input::Down, 0x7),
+        ),
+    RomInfo::new("turnover77", include_bytes!("../roms/turnover77.ch8")),
+];
+
+/// Looks up a bundled ROM by the index used in `MyState::Game`
+#[must_use]
+pub fn rom_info(id: u8) -> Option<&'static RomInfo> {
+    ROMS.get(id as usize)
+}
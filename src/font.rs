@@ -0,0 +1,241 @@
+use anyhow::Error;
+
+pub const SMALL_FONT_LEN: usize = 5 * 16;
+pub const BIG_FONT_LEN: usize = 10 * 16;
+
+// CHIP-48, also used by Super-Chip and Octo
+const CHIP48_FONT: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, //0
+    0x20, 0x60, 0x20, 0x20, 0x70, //1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, //2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, //3
+    0x90, 0x90, 0xF0, 0x10, 0x10, //4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, //5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, //6
+    0xF0, 0x10, 0x20, 0x40, 0x40, //7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, //8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, //9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, //A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, //B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, //C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, //D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, //E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, //F
+];
+
+// COSMAC VIP
+const VIP_FONT: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, //0
+    0x60, 0x20, 0x20, 0x20, 0x70, //1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, //2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, //3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, //4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, //5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, //6
+    0xF0, 0x10, 0x10, 0x10, 0x10, //7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, //8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, //9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, //A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, //B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, //C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, //D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, //E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, //F
+];
+
+// DREAM 6800, 3 pixels wide
+const DREAM_6800_FONT: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, //0
+    0x40, 0x40, 0x40, 0x40, 0x40, //1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, //2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, //3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, //4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, //5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, //6
+    0xE0, 0x20, 0x20, 0x20, 0x20, //7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, //8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, //9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, //A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, //B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, //C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, //D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, //E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, //F
+];
+
+// ETI-660, 3 pixels wide like the DREAM 6800 but with a different 1 and 4
+const ETI_660_FONT: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, //0
+    0x20, 0x20, 0x20, 0x20, 0x20, //1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, //2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, //3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, //4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, //5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, //6
+    0xE0, 0x20, 0x20, 0x20, 0x20, //7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, //8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, //9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, //A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, //B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, //C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, //D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, //E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, //F
+];
+
+// Octo, with A-F for XO-Chip
+const OCTO_BIG_FONT: [u8; BIG_FONT_LEN] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// Super-Chip 1.1 only has digits, A-F are Octo's so XO-Chip ROMs still find them
+const SCHIP_BIG_FONT: [u8; BIG_FONT_LEN] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Digit shapes of the original machines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontSet {
+    Vip,
+    Chip48,
+    Schip,
+    Dream6800,
+    Eti660,
+    #[default]
+    Octo,
+}
+
+impl FontSet {
+    /// 4x5 font FX29 points at
+    #[must_use]
+    pub const fn small(self) -> &'static [u8; SMALL_FONT_LEN] {
+        match self {
+            Self::Vip => &VIP_FONT,
+            Self::Chip48 | Self::Schip | Self::Octo => &CHIP48_FONT,
+            Self::Dream6800 => &DREAM_6800_FONT,
+            Self::Eti660 => &ETI_660_FONT,
+        }
+    }
+
+    /// 8x10 font FX30 points at, Octo's on machines that never had one
+    #[must_use]
+    pub const fn big(self) -> &'static [u8; BIG_FONT_LEN] {
+        match self {
+            Self::Schip => &SCHIP_BIG_FONT,
+            _ => &OCTO_BIG_FONT,
+        }
+    }
+}
+
+/// Fonts copied into memory when a ROM is loaded, and where they go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; SMALL_FONT_LEN],
+    pub big: [u8; BIG_FONT_LEN],
+    pub small_address: u16,
+    pub big_address: u16,
+}
+
+impl Font {
+    #[must_use]
+    pub const fn new(set: FontSet) -> Self {
+        Self::custom(*set.small(), *set.big())
+    }
+
+    /// 16 glyphs of 5 bytes and 16 glyphs of 10 bytes, from 0 to F
+    #[must_use]
+    pub const fn custom(small: [u8; SMALL_FONT_LEN], big: [u8; BIG_FONT_LEN]) -> Self {
+        Self {
+            small,
+            big,
+            small_address: 0x000,
+            big_address: 0x050,
+        }
+    }
+
+    /// Moves the fonts, the defaults are 0x000 and 0x050 right after the small font
+    #[must_use]
+    pub const fn at(mut self, small_address: u16, big_address: u16) -> Self {
+        self.small_address = small_address;
+        self.big_address = big_address;
+        self
+    }
+
+    /// Address of the small glyph for the low nibble of `digit`
+    #[inline]
+    #[must_use]
+    pub const fn small_glyph(&self, digit: u8) -> u16 {
+        self.small_address + (digit & 0x0F) as u16 * 5
+    }
+
+    /// Address of the big glyph for the low nibble of `digit`
+    #[inline]
+    #[must_use]
+    pub const fn big_glyph(&self, digit: u8) -> u16 {
+        self.big_address + (digit & 0x0F) as u16 * 10
+    }
+
+    /// Checks that both fonts fit in `memory_size` bytes without overlapping
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a font runs past the end of memory or into the other one
+    pub fn check(&self, memory_size: usize) -> Result<(), Error> {
+        let small = self.small_address as usize..self.small_address as usize + SMALL_FONT_LEN;
+        let big = self.big_address as usize..self.big_address as usize + BIG_FONT_LEN;
+
+        if small.end > memory_size || big.end > memory_size {
+            return Err(anyhow::anyhow!(
+                "Fonts at {:#05X} and {:#05X} don't fit in {memory_size:#06X} bytes",
+                self.small_address,
+                self.big_address,
+            ));
+        }
+
+        if small.start < big.end && big.start < small.end {
+            return Err(anyhow::anyhow!(
+                "Fonts at {:#05X} and {:#05X} overlap",
+                self.small_address,
+                self.big_address,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::new(FontSet::default())
+    }
+}
@@ -0,0 +1,190 @@
+pub const FRAMEBUFFER_WIDTH: usize = 128;
+pub const FRAMEBUFFER_HEIGHT: usize = 64;
+
+/// Monochrome framebuffer with one bit per pixel and one word per row
+///
+/// The leftmost pixel is the most significant bit of the row, so a screen narrower than 128
+/// pixels only uses the top bits and the rest stay clear. Sizes are powers of two, so wrapping
+/// around the edges is a mask instead of a division.
+///
+/// Every change marks the rows it touched as dirty, one bit per row, until the renderer takes
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    rows: [u128; FRAMEBUFFER_HEIGHT],
+    width: usize,
+    height: usize,
+    dirty: u64,
+}
+
+impl Framebuffer {
+    #[must_use]
+    pub const fn new(width: usize, height: usize) -> Self {
+        debug_assert!(width.is_power_of_two() && height.is_power_of_two());
+
+        Self {
+            rows: [0; FRAMEBUFFER_HEIGHT],
+            width,
+            height,
+            dirty: u64::MAX,
+        }
+    }
+
+    /// Changes the visible size, pixels outside of it are dropped
+    pub fn resize(&mut self, width: usize, height: usize) {
+        debug_assert!(width.is_power_of_two() && height.is_power_of_two());
+
+        self.width = width;
+        self.height = height;
+
+        let mask = self.mask();
+        for (y, row) in self.rows.iter_mut().enumerate() {
+            *row = if y < height { *row & mask } else { 0 };
+        }
+
+        self.mark_all_dirty();
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Bits of a row that are on screen
+    #[inline]
+    const fn mask(&self) -> u128 {
+        u128::MAX << (FRAMEBUFFER_WIDTH - self.width)
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; FRAMEBUFFER_HEIGHT];
+        self.mark_all_dirty();
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = u64::MAX;
+    }
+
+    /// Visible rows changed since the last `take_dirty_rows`, bit `y` for row `y`
+    #[inline]
+    #[must_use]
+    pub const fn dirty_rows(&self) -> u64 {
+        self.dirty & (u64::MAX >> (FRAMEBUFFER_HEIGHT - self.height))
+    }
+
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        let dirty = self.dirty_rows();
+        self.dirty = 0;
+        dirty
+    }
+
+    /// Visible rows, leftmost pixel in the most significant bit
+    #[inline]
+    #[must_use]
+    pub fn rows(&self) -> &[u128] {
+        &self.rows[..self.height]
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn row(&self, y: usize) -> u128 {
+        self.rows[y]
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << (FRAMEBUFFER_WIDTH - 1 - x)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        let bit = 1 << (FRAMEBUFFER_WIDTH - 1 - x);
+
+        if on {
+            self.rows[y] |= bit;
+        } else {
+            self.rows[y] &= !bit;
+        }
+
+        self.dirty |= 1 << y;
+    }
+
+    /// XORs a sprite row of `sprite_width` bits at (x, y), wrapping around the right edge
+    ///
+    /// Returns true if a pixel was turned off.
+    pub fn xor_row(&mut self, x: usize, y: usize, bits: u16, sprite_width: usize) -> bool {
+        let x = x & (self.width - 1);
+        let sprite = u128::from(bits) << (FRAMEBUFFER_WIDTH - sprite_width);
+
+        // Whatever falls off the right edge comes back on the left
+        let mut pixels = sprite >> x;
+        if x > 0 {
+            pixels |= sprite << (self.width - x);
+        }
+
+        self.xor_pixels(y, pixels & self.mask())
+    }
+
+    /// XORs a sprite row like `xor_row`, but cuts it off at the right edge instead of wrapping
+    pub fn xor_row_clipped(&mut self, x: usize, y: usize, bits: u16, sprite_width: usize) -> bool {
+        let x = x & (self.width - 1);
+        let sprite = u128::from(bits) << (FRAMEBUFFER_WIDTH - sprite_width);
+
+        self.xor_pixels(y, (sprite >> x) & self.mask())
+    }
+
+    fn xor_pixels(&mut self, y: usize, pixels: u128) -> bool {
+        let y = y & (self.height - 1);
+        let row = &mut self.rows[y];
+        let collision = *row & pixels != 0;
+        *row ^= pixels;
+
+        if pixels != 0 {
+            self.dirty |= 1 << y;
+        }
+
+        collision
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.height);
+        self.rows.copy_within(..self.height - n, n);
+        self.rows[..n].fill(0);
+        self.mark_all_dirty();
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.height);
+        self.rows.copy_within(n..self.height, 0);
+        self.rows[self.height - n..self.height].fill(0);
+        self.mark_all_dirty();
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        let mask = self.mask();
+        for row in &mut self.rows[..self.height] {
+            *row = (*row >> n) & mask;
+        }
+        self.mark_all_dirty();
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        for row in &mut self.rows[..self.height] {
+            *row <<= n;
+        }
+        self.mark_all_dirty();
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new(FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT)
+    }
+}
@@ -1,22 +1,74 @@
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use pd::controls::peripherals::{Accelerometer, Crank};
 use playdate_menu::api::Api;
 
 use super::*;
 
+/// Room left on the right of the screen for the keypad overlay
+const KEYPAD_WIDTH: usize = 144;
+const KEY_SIZE: i32 = 32;
+/// Instructions shown when the emulation fails
+#[cfg(feature = "debug-print-opcode")]
+const TRACE_LINES: usize = 32;
+
+// Options of the game menu item, the Playdate only has room for three items
+const GAME_OPTIONS: [&str; 3] = ["Play", "Restart", "Quit"];
+// ROMs played by tilting get one more, so the rest position can be taken again
+const TILT_GAME_OPTIONS: [&str; 4] = ["Play", "Restart", "Quit", "Calibrate"];
+const OPTION_RESTART: i32 = 1;
+const OPTION_QUIT: i32 = 2;
+const OPTION_CALIBRATE: i32 = 3;
+
 pub struct MyGame {
     pub on_state_change: Option<Box<dyn FnMut(MyState)>>,
     cpu: Chip8,
-    first: Option<SimpleMenuItem<u32>>,
+    game_item: Option<OptionsMenuItem<u32>>,
+    remap_item: Option<SimpleMenuItem<u32>>,
+    keypad_item: Option<SimpleMenuItem<u32>>,
+    should_exit: bool,
+    /// Names the saved key map
+    rom_name: Option<String>,
+    key_map: KeyMap,
+    paddle: Option<Paddle>,
+    tilt: Option<Tilt>,
+    remap: Option<RemapScreen>,
+    keypad: Keypad,
+    keypad_on_undock: bool,
+    was_docked: bool,
+    force_draw: bool,
+    renderer: Renderer,
+    sound: SoundPlayer,
+    #[cfg(feature = "debug-print-opcode")]
+    trace: Rc<RefCell<TraceRing>>,
 }
 
 impl Game for MyGame {
     fn new(_: &Playdate) -> Self {
-        let cpu = Chip8::new();
+        let mut cpu = Chip8::new();
+        cpu.set_instruction_cache(true);
 
         Self {
             on_state_change: None,
             cpu,
-            first: None,
+            game_item: None,
+            remap_item: None,
+            keypad_item: None,
+            should_exit: false,
+            rom_name: None,
+            key_map: KeyMap::default(),
+            paddle: None,
+            tilt: None,
+            remap: None,
+            keypad: Keypad::default(),
+            keypad_on_undock: false,
+            was_docked: true,
+            force_draw: false,
+            renderer: Renderer::new(),
+            sound: SoundPlayer::new(),
+            #[cfg(feature = "debug-print-opcode")]
+            trace: Rc::new(RefCell::new(TraceRing::new(TRACE_LINES))),
         }
     }
 
@@ -24,24 +76,79 @@ impl Game for MyGame {
     fn update(&mut self, _: &Playdate) {
         let mut should_remove = false;
 
-        if let Some(ref item) = self.first {
+        if self.should_exit {
+            self.should_exit = false;
+            if let Some(ref mut callback) = self.on_state_change {
+                callback(MyState::Menu);
+            }
+            return;
+        }
+
+        let mut option = 0;
+
+        if let Some(ref item) = self.game_item {
             if let Some(userdata) = item.get_userdata() {
                 if *userdata == 1 {
                     item.set_userdata(0);
-                    if let Some(ref mut callback) = self.on_state_change {
-                        callback(MyState::Menu);
-                        should_remove = true;
-                    }
+                    option = item.selected_option();
+                    // Back to "Play", so picking the same option again is a change
+                    item.set_selected_option(0);
                 }
             }
         }
 
+        match option {
+            OPTION_RESTART => self.restart(),
+            OPTION_CALIBRATE => {
+                if let Some(ref mut tilt) = self.tilt {
+                    tilt.calibrate();
+                }
+            }
+            OPTION_QUIT => {
+                // Nothing updates the player once the menu is back
+                self.sound.stop();
+                if let Some(ref mut callback) = self.on_state_change {
+                    callback(MyState::Menu);
+                    should_remove = true;
+                }
+            }
+            _ => {}
+        }
+
         if should_remove {
-            self.first = None;
+            self.game_item = None;
+            self.remap_item = None;
+            self.keypad_item = None;
+            self.remap = None;
+            return;
+        }
+
+        if let Some(ref item) = self.remap_item {
+            if let Some(userdata) = item.get_userdata() {
+                if *userdata == 1 {
+                    item.set_userdata(0);
+                    self.remap = Some(RemapScreen::new());
+                }
+            }
         }
 
+        if let Some(ref mut remap) = self.remap {
+            if !remap.update(&mut self.key_map) {
+                self.remap = None;
+                self.save_key_map();
+
+                // Wipe the list and draw the whole game again
+                Graphics::Cached().clear_raw(1);
+                self.force_draw = true;
+                System::Cached().reset_elapsed_time();
+            }
+            return;
+        }
+
+        self.update_keypad();
+
         #[cfg(feature = "debug-print-opcode")]
-        println!("{0:#04X}", self.cpu.get_opcode());
+        println!("{}", self.cpu.trace_entry());
 
         let graphics = Graphics::Cached();
         #[cfg(feature = "debug-profile")]
@@ -50,30 +157,71 @@ impl Game for MyGame {
         #[cfg(feature = "debug-profile")]
         let cpu_start = system.seconds_since_epoch_with_ms().1;
 
-        handle_inputs(&mut self.cpu);
+        handle_inputs(
+            &mut self.cpu,
+            &self.key_map,
+            self.paddle.as_mut(),
+            self.tilt.as_mut(),
+            &self.keypad,
+        );
 
-        match self.cpu.update() {
-            Ok(()) => {}
-            Err(e) => {
-                println!("{}", e);
+        // Always take the time, so a paused keypad doesn't pile it up
+        let system = System::Cached();
+        let elapsed_micros = (system.elapsed_time() * 1_000_000.0) as u32;
+        system.reset_elapsed_time();
+
+        if self.keypad.should_run() {
+            match self.cpu.run_for(elapsed_micros) {
+                Ok(()) => {}
+                Err(e) => {
+                    #[cfg(feature = "debug-print-opcode")]
+                    for entry in self.trace.borrow().entries() {
+                        println!("{}", entry);
+                    }
+                    println!("{}", e);
+                }
             }
         }
 
+        if self.force_draw {
+            self.force_draw = false;
+            self.cpu.full_screen_rows();
+            self.cpu.should_draw = true;
+        }
+
         #[cfg(feature = "debug-profile")]
         let cpu_time = system.seconds_since_epoch_with_ms().1 - cpu_start;
 
         if self.cpu.play_sound() {
             // TODO: Add beep
         }
+        self.sound.update(self.cpu.sample());
 
-        let scale = if self.cpu.is_hi_res() { 3 } else { 6 };
-        let lcd_width = if self.cpu.is_hi_res() { 128 } else { 64 };
-        let lcd_height = if self.cpu.is_hi_res() { 64 } else { 32 };
+        // Shrink the game to make room for the keypad
+        let area_width = if self.keypad.is_open() {
+            LCD_COLUMNS as usize - KEYPAD_WIDTH
+        } else {
+            LCD_COLUMNS as usize
+        };
+        let lcd_width = self.cpu.width();
+        let lcd_height = self.cpu.height();
+        // Biggest whole scale that fits: 6 for 64x32, 3 for 128x64 and 64x64
+        let scale = (area_width / lcd_width).min(LCD_ROWS as usize / lcd_height);
 
         #[cfg(feature = "debug-profile")]
         let gpu_start = system.seconds_since_epoch_with_ms().1;
 
-        draw(graphics, &mut self.cpu, scale, lcd_width, lcd_height);
+        // The Mega-Chip screen only fits unscaled, and still does next to the keypad
+        if self.cpu.mega_screen().is_some() {
+            self.renderer.set_layout(MEGA_WIDTH, MEGA_HEIGHT, 1, area_width);
+        } else {
+            self.renderer.set_layout(lcd_width, lcd_height, scale, area_width);
+        }
+        draw(graphics, &mut self.cpu, &mut self.renderer);
+
+        if self.keypad.is_open() {
+            draw_keypad(graphics, &self.keypad);
+        }
 
         #[cfg(feature = "debug-profile")]
         let gpu_time = system.seconds_since_epoch_with_ms().1 - gpu_start;
@@ -84,115 +232,91 @@ impl Game for MyGame {
             .unwrap();
 
         #[cfg(feature = "debug-profile")]
-        println!("CPU: {}, GPU: {}!", cpu_time, gpu_time);
+        println!(
+            "CPU: {}, GPU: {}, skipped: {}!",
+            cpu_time,
+            gpu_time,
+            self.cpu.skipped_cycles()
+        );
 
         System::Cached().draw_fps(0, 0);
     }
 }
 
-pub fn handle_inputs(cpu: &mut Chip8) {
-    let buttons = Buttons::Cached();
-
-    let (up, right, down, left, a, b) = (5, 9, 8, 7, 6, 10);
+pub fn handle_inputs(
+    cpu: &mut Chip8,
+    key_map: &KeyMap,
+    paddle: Option<&mut Paddle>,
+    tilt: Option<&mut Tilt>,
+    keypad: &Keypad,
+) {
+    let buttons = Buttons::Cached().current();
+    let crank = Crank::Cached();
+
+    let change = crank.change();
+    let docked = crank.docked();
+
+    let mut active = [false; INPUTS];
+    active[Input::Up as usize] = buttons.up();
+    active[Input::Right as usize] = buttons.right();
+    active[Input::Down as usize] = buttons.down();
+    active[Input::Left as usize] = buttons.left();
+    active[Input::A as usize] = buttons.a();
+    active[Input::B as usize] = buttons.b();
+    active[Input::CrankForward as usize] = change > CRANK_THRESHOLD;
+    active[Input::CrankBackward as usize] = change < -CRANK_THRESHOLD;
+    active[Input::CrankDocked as usize] = docked;
+    active[Input::CrankUndocked as usize] = !docked;
+
+    // The keypad takes over the d-pad and A while it's open
+    if keypad.is_open() {
+        for input in [Input::Up, Input::Right, Input::Down, Input::Left, Input::A] {
+            active[input as usize] = false;
+        }
+    }
 
-    // TODO: Needs refactor
+    key_map.apply(&active, &mut cpu.keys);
+    // Only the keypad reaches the second keypad of CHIP-8X
+    cpu.second_keys = [false; KEYS];
+    keypad.apply(&mut cpu.keys, &mut cpu.second_keys);
 
-    if buttons.pushed().up() {
-        cpu.keys[up] = true;
-    }
-    if buttons.pushed().right() {
-        cpu.keys[right] = true;
-    }
-    if buttons.pushed().down() {
-        cpu.keys[down] = true;
-    }
-    if buttons.pushed().left() {
-        cpu.keys[left] = true;
-    }
-    if buttons.pushed().a() {
-        cpu.keys[a] = true;
-    }
-    if buttons.pushed().b() {
-        cpu.keys[b] = true;
+    // The paddle only takes over while the crank is out
+    if let Some(paddle) = paddle {
+        if !docked {
+            paddle.apply(change, &mut cpu.keys);
+        }
     }
 
-    if buttons.released().up() {
-        cpu.keys[up] = false;
-    }
-    if buttons.released().right() {
-        cpu.keys[right] = false;
-    }
-    if buttons.released().down() {
-        cpu.keys[down] = false;
-    }
-    if buttons.released().left() {
-        cpu.keys[left] = false;
-    }
-    if buttons.released().a() {
-        cpu.keys[a] = false;
-    }
-    if buttons.released().b() {
-        cpu.keys[b] = false;
+    if let Some(tilt) = tilt {
+        let (x, y, _) = Accelerometer::Cached().get();
+        tilt.apply(x, y, &mut cpu.keys);
     }
 }
 
-pub fn draw(graphics: Graphics<Cache>, cpu: &mut Chip8, scale: usize, width: usize, height: usize) {
-    let frame = graphics.get_frame().unwrap();
-
-    if cpu.should_draw {
-        // Calculate padding to center the framebuffer
-        let padding_x = (LCD_COLUMNS as usize - (width * scale)) / 2; // Horizontal padding
-        let padding_y = (LCD_ROWS as usize - (height * scale)) / 2; // Vertical padding
-
-        for y in 0..height {
-            for x in 0..width {
-                let pixel =
-                    !cpu.gfx_buffer[(y * width + x + cpu.scroll_x as usize) % cpu.gfx_buffer.len()];
-
-                // Draw a scaled block for each pixel
-                for sy in 0..scale {
-                    for sx in 0..scale {
-                        let scaled_x = padding_x + x * scale + sx; // Add horizontal padding
-                        let scaled_y = padding_y + y * scale + sy; // Add vertical padding
-
-                        // Calculate the position in the frame
-                        let byte_index = (scaled_y * LCD_ROWSIZE as usize) + (scaled_x / 8);
-                        let bit_index = 7 - (scaled_x % 8); // Bit order is reversed in each byte
-
-                        if pixel {
-                            frame[byte_index] |= 1 << bit_index; // Set the pixel
-                        } else {
-                            frame[byte_index] &= !(1 << bit_index); // Clear the pixel
-                        }
-                    }
-                }
-            }
-        }
+pub fn draw(graphics: Graphics<Cache>, cpu: &mut Chip8, renderer: &mut Renderer) {
+    if !cpu.should_draw {
+        return;
+    }
 
-        // Update the rows, including padding offset
-        graphics.mark_updated_rows(0, 240);
+    let frame = graphics.get_frame().unwrap();
 
-        #[cfg(feature = "debug-gfx")]
-        graphics.draw_line(
-            0,
-            ((cpu.rows_start * scale as u8) + padding_y as u8).into(),
-            400,
-            ((cpu.rows_start * scale as u8) + padding_y as u8).into(),
-            1,
-            0,
-        );
+    let mark = |start: usize, end: usize| {
+        graphics.mark_updated_rows(start as i32, end as i32);
 
+        // Outline every run of redrawn rows
         #[cfg(feature = "debug-gfx")]
-        graphics.draw_line(
-            0,
-            ((cpu.rows_end * scale as u8) + padding_y as u8).into(),
-            400,
-            ((cpu.rows_end * scale as u8) + padding_y as u8).into(),
-            1,
-            0,
-        );
-
-        cpu.reset_rows();
+        {
+            graphics.draw_line(0, start as i32, 400, start as i32, 1, 0);
+            graphics.draw_line(0, end as i32, 400, end as i32, 1, 0);
+        }
+    };
+
+    if let Some(mega) = cpu.mega_screen_mut() {
+        renderer.render_mega(mega, &mut frame[..], mark);
+    } else if cpu.variant() == Variant::Chip8X {
+        renderer.render_colors(&mut cpu.gfx_buffer, &cpu.color_map, &mut frame[..], mark);
+    } else {
+        renderer.render(&mut cpu.gfx_buffer, &mut frame[..], mark);
     }
 }
 
@@ -200,8 +324,96 @@ impl MyGame {
     pub fn on_enter(&mut self, id: u8) {
         println!("Entered Game state");
 
-        self.cpu
-            .load_rom(include_bytes!("../roms/sweetcopter.ch8"), Some(200));
+        // Can't change state from here, the main loop is still switching to us
+        let Some(info) = rom_info(id) else {
+            println!("Unknown ROM {}", id);
+            self.should_exit = true;
+            return;
+        };
+
+        self.start(info.name, info, info.rom, info.variant.detect(info.rom));
+    }
+
+    /// Starts a ROM copied into the data folder, with the default settings of `RomInfo::new`
+    pub fn on_enter_data(&mut self, name: &str) {
+        println!("Entered Game state");
+
+        let rom = match RomSource::data_folder(name).read() {
+            Ok(rom) => rom,
+            Err(e) => {
+                println!("{}", e);
+                self.should_exit = true;
+                return;
+            }
+        };
+
+        // Not in the database, so the opcodes it uses are all there is to go on
+        let variant = analyze_unknown(&rom).recommended_variant();
+        let info = RomInfo::new("", &[]);
+
+        self.start(name, &info, &rom, variant);
+    }
+
+    fn start(&mut self, name: &str, info: &RomInfo, rom: &[u8], variant: Variant) {
+        // Running still works up to the first opcode the core lacks
+        let entry_point = MemoryMap::for_variant(variant).entry_point;
+        if let Some(instruction) = analyze(rom, entry_point).unsupported().next() {
+            println!(
+                "{} uses unsupported opcode {:#06X} at {:#05X}",
+                name, instruction.opcode, instruction.address
+            );
+        }
+
+        // Only as much memory as the original machine had
+        self.cpu = Chip8::with_memory_map(MemoryMap::for_variant(variant));
+        self.cpu.set_instruction_cache(true);
+        self.cpu.set_variant(variant);
+
+        #[cfg(feature = "debug-print-opcode")]
+        {
+            self.trace.borrow_mut().clear();
+            self.cpu.set_tracer(Some(self.trace.clone()));
+        }
+
+        if let Err(e) = self.cpu.set_font(Font::new(info.font)) {
+            println!("{}", e);
+            self.should_exit = true;
+            return;
+        }
+
+        if let Err(e) = self.cpu.load_rom(rom, None) {
+            println!("{}", e);
+            self.should_exit = true;
+            return;
+        }
+
+        self.cpu.set_speed(info.speed);
+        self.cpu.set_timing(info.timing);
+        System::Cached().reset_elapsed_time();
+
+        self.rom_name = Some(String::from(name));
+        self.key_map = load_key_map(name, info.key_map);
+        self.paddle = info.paddle;
+        self.tilt = info.tilt;
+
+        // Reading the accelerometer costs battery, only keep it on for ROMs that use it
+        if self.tilt.is_some() {
+            Accelerometer::Cached().enable();
+        } else {
+            Accelerometer::Cached().disable();
+        }
+        self.remap = None;
+        self.force_draw = true;
+
+        self.keypad = Keypad::new(info.keypad_pauses);
+        if variant == Variant::Chip8X {
+            self.keypad = self.keypad.with_second_keypad();
+        }
+        // Undocking opens the keypad, unless the crank is already used by the game
+        self.keypad_on_undock = info.paddle.is_none()
+            && self.key_map.keys(Input::CrankDocked) == 0
+            && self.key_map.keys(Input::CrankUndocked) == 0;
+        self.was_docked = Crank::Cached().docked();
 
         let ms = System::Cached().seconds_since_epoch();
         self.cpu.set_random_seed(ms as u64);
@@ -210,9 +422,91 @@ impl MyGame {
             *userdata = 1;
         };
 
-        self.first = SimpleMenuItem::new("Check Me", Some(on_change), 0)
+        let game_item = if self.tilt.is_some() {
+            OptionsMenuItem::new("Game", TILT_GAME_OPTIONS, Some(on_change), 0)
+        } else {
+            OptionsMenuItem::new("Game", GAME_OPTIONS, Some(on_change), 0)
+        };
+        self.game_item = game_item.unwrap().into();
+
+        self.remap_item = SimpleMenuItem::new("Remap keys", Some(on_change), 0)
             .unwrap()
             .into();
+
+        self.keypad_item = SimpleMenuItem::new("Keypad", Some(on_change), 0)
+            .unwrap()
+            .into();
+    }
+
+    /// Runs the ROM again from the start, like pressing reset on the machine
+    fn restart(&mut self) {
+        if let Err(e) = self.cpu.warm_reset() {
+            println!("{}", e);
+            return;
+        }
+
+        self.force_draw = true;
+        System::Cached().reset_elapsed_time();
+    }
+
+    /// Opens and closes the keypad and moves its cursor
+    fn update_keypad(&mut self) {
+        let was_open = self.keypad.is_open();
+
+        if let Some(ref item) = self.keypad_item {
+            if let Some(userdata) = item.get_userdata() {
+                if *userdata == 1 {
+                    item.set_userdata(0);
+                    self.keypad.open();
+                }
+            }
+        }
+
+        let docked = Crank::Cached().docked();
+        if self.keypad_on_undock && docked != self.was_docked {
+            if docked {
+                self.keypad.close();
+            } else {
+                self.keypad.open();
+            }
+        }
+        self.was_docked = docked;
+
+        if self.keypad.is_open() {
+            let buttons = Buttons::Cached();
+            let pushed = buttons.pushed();
+
+            if pushed.left() {
+                self.keypad.left();
+            }
+            if pushed.right() {
+                self.keypad.right();
+            }
+            if pushed.up() {
+                self.keypad.up();
+            }
+            if pushed.down() {
+                self.keypad.down();
+            }
+            if pushed.b() {
+                self.keypad.close();
+            }
+
+            self.keypad.set_pressed(buttons.current().a());
+        }
+
+        if self.keypad.is_open() != was_open {
+            Graphics::Cached().clear_raw(1);
+            self.force_draw = true;
+        }
+    }
+
+    fn save_key_map(&self) {
+        if let Some(ref name) = self.rom_name {
+            if let Err(e) = write_data(&key_map_path(name), &self.key_map.to_bytes()) {
+                println!("{}", e);
+            }
+        }
     }
 
     pub fn set_on_state_change<F>(&mut self, callback: F)
@@ -222,3 +516,47 @@ impl MyGame {
         self.on_state_change = Some(Box::new(callback));
     }
 }
+
+pub fn draw_keypad(graphics: Graphics<Cache>, keypad: &Keypad) {
+    let left = LCD_COLUMNS as i32 - KEYPAD_WIDTH as i32 + 8;
+    let top = (LCD_ROWS as i32 - KEY_SIZE * 4) / 2;
+
+    if keypad.has_second_keypad() {
+        let number = if keypad.is_second() { 2 } else { 1 };
+        graphics
+            .draw_text(format!("Keypad {number}"), left, top - 24)
+            .unwrap();
+    }
+
+    for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (column, key) in keys.iter().enumerate() {
+            let x = left + column as i32 * KEY_SIZE;
+            let y = top + row as i32 * KEY_SIZE;
+
+            graphics.fill_rect(x, y, KEY_SIZE, KEY_SIZE, 1);
+            graphics.draw_rect(x + 2, y + 2, KEY_SIZE - 4, KEY_SIZE - 4, 0);
+
+            if keypad.cursor() == (column, row) {
+                graphics.draw_rect(x + 4, y + 4, KEY_SIZE - 8, KEY_SIZE - 8, 0);
+
+                if keypad.is_pressed() {
+                    graphics.fill_rect(x + 8, y + KEY_SIZE - 10, KEY_SIZE - 16, 3, 0);
+                }
+            }
+
+            graphics.draw_text(format!("{key:X}"), x + 12, y + 7).unwrap();
+        }
+    }
+}
+
+fn key_map_path(name: &str) -> String {
+    format!("{name}.keys")
+}
+
+/// Key map saved by the remap screen, or the database default
+fn load_key_map(name: &str, default: KeyMap) -> KeyMap {
+    read_data(&key_map_path(name))
+        .ok()
+        .and_then(|bytes| KeyMap::from_bytes(&bytes))
+        .unwrap_or(default)
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+pub const INPUTS: usize = 10;
+
+/// Degrees the crank has to turn in a frame to count as a crank input
+pub const CRANK_THRESHOLD: f32 = 1.0;
+
+/// Playdate input that can be bound to CHIP-8 keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Up,
+    Right,
+    Down,
+    Left,
+    A,
+    B,
+    CrankForward,
+    CrankBackward,
+    CrankDocked,
+    CrankUndocked,
+}
+
+impl Input {
+    pub const ALL: [Self; INPUTS] = [
+        Self::Up,
+        Self::Right,
+        Self::Down,
+        Self::Left,
+        Self::A,
+        Self::B,
+        Self::CrankForward,
+        Self::CrankBackward,
+        Self::CrankDocked,
+        Self::CrankUndocked,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Up => "Up",
+            Self::Right => "Right",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::A => "A",
+            Self::B => "B",
+            Self::CrankForward => "Crank forward",
+            Self::CrankBackward => "Crank backward",
+            Self::CrankDocked => "Crank docked",
+            Self::CrankUndocked => "Crank undocked",
+        }
+    }
+}
+
+/// Which inputs are active this frame, indexed by `Input`
+pub type ActiveInputs = [bool; INPUTS];
+
+/// Binds each Playdate input to a set of CHIP-8 keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMap {
+    // One bit per CHIP-8 key
+    bindings: [u16; INPUTS],
+}
+
+impl KeyMap {
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            bindings: [0; INPUTS],
+        }
+    }
+
+    /// Same layout as Octo: WASD on the d-pad, E on A and Z on B
+    #[must_use]
+    pub const fn octo() -> Self {
+        Self::empty()
+            .with(Input::Up, 0x5)
+            .with(Input::Right, 0x9)
+            .with(Input::Down, 0x8)
+            .with(Input::Left, 0x7)
+            .with(Input::A, 0x6)
+            .with(Input::B, 0xA)
+    }
+
+    /// Adds a binding, meant for building maps in constants
+    #[must_use]
+    pub const fn with(mut self, input: Input, key: u8) -> Self {
+        self.bindings[input as usize] |= 1 << (key & 0xF);
+        self
+    }
+
+    #[must_use]
+    pub const fn is_bound(&self, input: Input, key: u8) -> bool {
+        self.bindings[input as usize] & (1 << (key & 0xF)) != 0
+    }
+
+    pub fn toggle(&mut self, input: Input, key: u8) {
+        self.bindings[input as usize] ^= 1 << (key & 0xF);
+    }
+
+    /// Bitmask of the keys bound to the input
+    #[must_use]
+    pub const fn keys(&self, input: Input) -> u16 {
+        self.bindings[input as usize]
+    }
+
+    /// Presses every key bound to an active input and releases all the others
+    pub fn apply(&self, active: &ActiveInputs, keys: &mut [bool; KEYS]) {
+        let mut pressed = 0;
+
+        for (bits, _) in self.bindings.iter().zip(active).filter(|(_, on)| **on) {
+            pressed |= bits;
+        }
+
+        for (key, state) in keys.iter_mut().enumerate() {
+            *state = pressed & (1 << key) != 0;
+        }
+    }
+
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; INPUTS * 2] {
+        let mut bytes = [0; INPUTS * 2];
+
+        for (chunk, bits) in bytes.chunks_exact_mut(2).zip(self.bindings) {
+            chunk.copy_from_slice(&bits.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Reads a map written by `to_bytes`, returns `None` if the size doesn't match
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != INPUTS * 2 {
+            return None;
+        }
+
+        let mut key_map = Self::empty();
+
+        for (bits, chunk) in key_map.bindings.iter_mut().zip(bytes.chunks_exact(2)) {
+            *bits = u16::from_le_bytes([chunk[0], chunk[1]]);
+        }
+
+        Some(key_map)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::octo()
+    }
+}
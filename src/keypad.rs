@@ -0,0 +1,130 @@
+use super::*;
+
+/// Keys in the order of the COSMAC VIP hex keypad
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// On-screen hex keypad for ROMs that need more keys than the Playdate has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keypad {
+    row: usize,
+    column: usize,
+    pressed: bool,
+    open: bool,
+    /// 8 when the cursor can go on past the right edge to the second keypad of CHIP-8X
+    columns: usize,
+    /// Emulation only runs while a key is pressed
+    pub pauses: bool,
+}
+
+impl Keypad {
+    #[must_use]
+    pub const fn new(pauses: bool) -> Self {
+        Self {
+            row: 0,
+            column: 0,
+            pressed: false,
+            open: false,
+            columns: 4,
+            pauses,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_second_keypad(mut self) -> Self {
+        self.columns = 8;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn has_second_keypad(&self) -> bool {
+        self.columns == 8
+    }
+
+    /// Whether the cursor is on the second keypad
+    #[inline]
+    #[must_use]
+    pub const fn is_second(&self) -> bool {
+        self.column >= 4
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.pressed = false;
+    }
+
+    // The cursor wraps around the edges, going through the second keypad if there is one
+
+    pub fn left(&mut self) {
+        self.column = (self.column + self.columns - 1) % self.columns;
+    }
+
+    pub fn right(&mut self) {
+        self.column = (self.column + 1) % self.columns;
+    }
+
+    pub fn up(&mut self) {
+        self.row = (self.row + 3) % 4;
+    }
+
+    pub fn down(&mut self) {
+        self.row = (self.row + 1) % 4;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn cursor(&self) -> (usize, usize) {
+        (self.column % 4, self.row)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn selected(&self) -> u8 {
+        KEYPAD_LAYOUT[self.row][self.column % 4]
+    }
+
+    pub fn set_pressed(&mut self, pressed: bool) {
+        self.pressed = pressed && self.open;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Whether the interpreter should run this frame
+    #[must_use]
+    pub const fn should_run(&self) -> bool {
+        !(self.open && self.pauses) || self.pressed
+    }
+
+    /// Presses the selected key on top of the keys already pressed, on the keypad the cursor is on
+    pub fn apply(&self, keys: &mut [bool; KEYS], second_keys: &mut [bool; KEYS]) {
+        if self.pressed {
+            let keys = if self.is_second() { second_keys } else { keys };
+            keys[self.selected() as usize] = true;
+        }
+    }
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
@@ -8,13 +8,14 @@ extern crate playdate_menu;
 use core::cell::RefCell;
 
 use alloc::rc::Rc;
+use alloc::string::String;
 use crankit_game_loop::{game_loop, Game, Playdate};
 use pd::controls::buttons::PDButtonsExt;
 use pd::controls::peripherals::Buttons;
 use pd::graphics::api::Cache;
 use pd::graphics::Graphics;
 use pd::println;
-use pd::sys::ffi::{LCD_COLUMNS, LCD_ROWS, LCD_ROWSIZE};
+use pd::sys::ffi::{LCD_COLUMNS, LCD_ROWS};
 use pd::system::prelude::*;
 use playdate_menu::*;
 
@@ -24,13 +25,69 @@ use game::*;
 pub mod pachip8risu;
 use pachip8risu::*;
 
+pub mod framebuffer;
+use framebuffer::*;
+
 pub mod menu;
 use menu::*;
 
-#[derive(Debug, Clone, Copy)]
+pub mod rom;
+use rom::*;
+
+pub mod storage;
+use storage::*;
+
+pub mod keymap;
+use keymap::*;
+
+pub mod database;
+use database::*;
+
+pub mod remap;
+use remap::*;
+
+pub mod paddle;
+use paddle::*;
+
+pub mod tilt;
+use tilt::*;
+
+pub mod keypad;
+use keypad::*;
+
+pub mod renderer;
+use renderer::*;
+
+pub mod random;
+use random::*;
+
+pub mod megachip;
+use megachip::*;
+
+pub mod chip8x;
+use chip8x::*;
+
+pub mod font;
+use font::*;
+
+pub mod analyzer;
+use analyzer::*;
+
+pub mod cfg;
+use cfg::*;
+
+pub mod trace;
+use trace::*;
+
+pub mod sound;
+use sound::*;
+
+#[derive(Debug, Clone)]
 pub enum MyState {
     Menu,
     Game(u8),
+    /// A ROM from the data folder, by file name
+    DataRom(String),
 }
 
 pub struct MyMain {
@@ -72,6 +129,7 @@ impl Game for MyMain {
             match new_state {
                 MyState::Menu => self.menu.on_enter(),
                 MyState::Game(id) => self.game.on_enter(id),
+                MyState::DataRom(ref name) => self.game.on_enter_data(name),
             }
             *self.state.borrow_mut() = new_state;
         }
@@ -79,7 +137,7 @@ impl Game for MyMain {
         // Call update based on current state
         match *self.state.borrow() {
             MyState::Menu => self.menu.update(pd),
-            MyState::Game(_) => self.game.update(pd),
+            MyState::Game(_) | MyState::DataRom(_) => self.game.update(pd),
         }
     }
 }
@@ -0,0 +1,256 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+const MEGA_PIXELS: usize = MEGA_WIDTH * MEGA_HEIGHT;
+
+/// How sprite pixels mix with the screen under them, set by 080N
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Opaque, unless the palette color has alpha
+    #[default]
+    Normal,
+    Quarter,
+    Half,
+    ThreeQuarters,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    #[must_use]
+    pub const fn from_nibble(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Quarter),
+            2 => Some(Self::Half),
+            3 => Some(Self::ThreeQuarters),
+            4 => Some(Self::Add),
+            5 => Some(Self::Multiply),
+            _ => None,
+        }
+    }
+}
+
+/// Digitised sound started by 060N
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// First byte of the 8-bit unsigned samples
+    pub address: usize,
+    /// Samples per second
+    pub rate: u16,
+    pub len: usize,
+    pub looping: bool,
+}
+
+/// The 256x192 screen of Mega-Chip mode
+///
+/// Sprites draw into a back buffer, and 00E0 shows it and starts the next frame, so demos never
+/// show a half drawn frame. The back buffer keeps both the palette index of each pixel, which
+/// collisions check, and its color after blending.
+#[derive(Debug, Clone)]
+pub struct MegaScreen {
+    indices: Box<[u8]>,
+    colors: Box<[u32]>,
+    front: Box<[u32]>,
+    // ARGB, index 0 is transparent
+    palette: [u32; 256],
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    pub blend: BlendMode,
+    /// Fades the whole screen, 255 is fully visible
+    pub alpha: u8,
+    /// Drawing over this palette index sets VF, nothing collides until 09NN picks one
+    pub collision_color: Option<u8>,
+    pub sample: Option<Sample>,
+    presented: bool,
+}
+
+impl MegaScreen {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            indices: vec![0; MEGA_PIXELS].into_boxed_slice(),
+            colors: vec![0; MEGA_PIXELS].into_boxed_slice(),
+            front: vec![0; MEGA_PIXELS].into_boxed_slice(),
+            palette: [0; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            blend: BlendMode::Normal,
+            alpha: 0xFF,
+            collision_color: None,
+            sample: None,
+            presented: true,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn palette(&self) -> &[u32; 256] {
+        &self.palette
+    }
+
+    /// Loads ARGB colors, 4 bytes each, from index 1 on
+    pub fn load_palette(&mut self, bytes: &[u8]) {
+        for (color, argb) in self.palette[1..].iter_mut().zip(bytes.chunks_exact(4)) {
+            *color = u32::from_be_bytes([argb[0], argb[1], argb[2], argb[3]]);
+        }
+    }
+
+    /// Color of a pixel on the shown frame
+    #[inline]
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.front[x + y * MEGA_WIDTH]
+    }
+
+    /// Palette index of a pixel on the frame being drawn
+    #[inline]
+    #[must_use]
+    pub fn index(&self, x: usize, y: usize) -> u8 {
+        self.indices[x + y * MEGA_WIDTH]
+    }
+
+    pub fn clear(&mut self) {
+        self.indices.fill(0);
+        self.colors.fill(0);
+    }
+
+    /// Shows the frame drawn so far
+    pub fn present(&mut self) {
+        self.front.copy_from_slice(&self.colors);
+        self.presented = true;
+    }
+
+    /// Whether a frame was shown since the last call
+    pub fn take_presented(&mut self) -> bool {
+        core::mem::replace(&mut self.presented, false)
+    }
+
+    /// Draws a `sprite_width` by `sprite_height` sprite of palette indices, clipped at the edges
+    ///
+    /// `sprite` returns the byte at an offset into the sprite. Index 0 is transparent. Returns
+    /// true if a pixel of the collision color was drawn over.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: impl Fn(usize) -> u8) -> bool {
+        let mut collision = false;
+
+        for row in 0..self.sprite_height {
+            let screen_y = y + row;
+            if screen_y >= MEGA_HEIGHT {
+                break;
+            }
+
+            for column in 0..self.sprite_width {
+                let screen_x = x + column;
+                if screen_x >= MEGA_WIDTH {
+                    break;
+                }
+
+                let index = sprite(row * self.sprite_width + column);
+                if index == 0 {
+                    continue;
+                }
+
+                let pixel = screen_x + screen_y * MEGA_WIDTH;
+                collision |= Some(self.indices[pixel]) == self.collision_color;
+
+                self.indices[pixel] = index;
+                self.colors[pixel] = self.mix(self.palette[index as usize], self.colors[pixel]);
+            }
+        }
+
+        collision
+    }
+
+    fn mix(&self, source: u32, destination: u32) -> u32 {
+        let opacity = match self.blend {
+            BlendMode::Normal => source >> 24,
+            BlendMode::Quarter => 0x40,
+            BlendMode::Half => 0x80,
+            BlendMode::ThreeQuarters => 0xC0,
+            BlendMode::Add | BlendMode::Multiply => 0,
+        };
+
+        let channel = |shift: u32| {
+            let s = (source >> shift) & 0xFF;
+            let d = (destination >> shift) & 0xFF;
+
+            let mixed = match self.blend {
+                BlendMode::Add => (s + d).min(0xFF),
+                BlendMode::Multiply => s * d / 0xFF,
+                _ => (s * opacity + d * (0xFF - opacity)) / 0xFF,
+            };
+            mixed << shift
+        };
+
+        0xFF00_0000 | channel(16) | channel(8) | channel(0)
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(MEGA_HEIGHT) * MEGA_WIDTH;
+        self.indices.copy_within(..MEGA_PIXELS - n, n);
+        self.indices[..n].fill(0);
+        self.colors.copy_within(..MEGA_PIXELS - n, n);
+        self.colors[..n].fill(0);
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(MEGA_HEIGHT) * MEGA_WIDTH;
+        self.indices.copy_within(n.., 0);
+        self.indices[MEGA_PIXELS - n..].fill(0);
+        self.colors.copy_within(n.., 0);
+        self.colors[MEGA_PIXELS - n..].fill(0);
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        let n = n.min(MEGA_WIDTH);
+        for row in self.indices.chunks_exact_mut(MEGA_WIDTH) {
+            row.copy_within(..MEGA_WIDTH - n, n);
+            row[..n].fill(0);
+        }
+        for row in self.colors.chunks_exact_mut(MEGA_WIDTH) {
+            row.copy_within(..MEGA_WIDTH - n, n);
+            row[..n].fill(0);
+        }
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        let n = n.min(MEGA_WIDTH);
+        for row in self.indices.chunks_exact_mut(MEGA_WIDTH) {
+            row.copy_within(n.., 0);
+            row[MEGA_WIDTH - n..].fill(0);
+        }
+        for row in self.colors.chunks_exact_mut(MEGA_WIDTH) {
+            row.copy_within(n.., 0);
+            row[MEGA_WIDTH - n..].fill(0);
+        }
+    }
+}
+
+impl Default for MegaScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Perceived brightness of an ARGB color faded by `alpha`, from 0 to 255
+#[inline]
+#[must_use]
+pub const fn luminance(color: u32, alpha: u8) -> u32 {
+    let r = (color >> 16) & 0xFF;
+    let g = (color >> 8) & 0xFF;
+    let b = color & 0xFF;
+
+    ((77 * r + 150 * g + 29 * b) >> 8) * alpha as u32 / 0xFF
+}
+
+/// 8-bit unsigned samples as 16-bit signed little endian ones, which the Playdate plays
+#[must_use]
+pub fn to_pcm16(samples: &[u8]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&sample| ((i16::from(sample) - 128) << 8).to_le_bytes())
+        .collect()
+}
@@ -1,4 +1,7 @@
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use pd::{
     graphics::text::TextAlignmentExt,
     sys::ffi::{PDTextAlignment, PDTextWrappingMode},
@@ -6,14 +9,21 @@ use pd::{
 
 use super::*;
 
+const VISIBLE_ROMS: usize = 9;
+
 pub struct MyMenu {
     pub on_state_change: Option<Box<dyn FnMut(MyState)>>,
+    selected: usize,
+    /// Listed after the bundled ROMs
+    data_roms: Vec<String>,
 }
 
 impl Game for MyMenu {
     fn new(_: &Playdate) -> Self {
         Self {
             on_state_change: None,
+            selected: 0,
+            data_roms: data_folder_roms(),
         }
     }
 
@@ -22,23 +32,55 @@ impl Game for MyMenu {
 
         System::Cached().draw_fps(0, 0);
 
-        Graphics::Cached()
-            .draw_text_in_rect(
-                "This is the menu",
-                0,
-                120 - 8,
-                400,
-                16,
-                PDTextWrappingMode::kWrapClip,
-                PDTextAlignment::Center,
-            )
-            .unwrap();
-
         let buttons = Buttons::Cached();
+        let count = ROMS.len() + self.data_roms.len();
 
         if buttons.pushed().up() {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if buttons.pushed().down() {
+            self.selected = (self.selected + 1) % count;
+        }
+
+        // Keep the selection in the middle of the list
+        let first = self
+            .selected
+            .saturating_sub(VISIBLE_ROMS / 2)
+            .min(count.saturating_sub(VISIBLE_ROMS));
+
+        let names = ROMS
+            .iter()
+            .map(|info| info.name)
+            .chain(self.data_roms.iter().map(String::as_str));
+
+        for (row, name) in names.enumerate().skip(first).take(VISIBLE_ROMS) {
+            let text = if row == self.selected {
+                format!("> {} <", name)
+            } else {
+                String::from(name)
+            };
+
+            Graphics::Cached()
+                .draw_text_in_rect(
+                    text,
+                    0,
+                    30 + (row - first) as i32 * 20,
+                    400,
+                    16,
+                    PDTextWrappingMode::kWrapClip,
+                    PDTextAlignment::Center,
+                )
+                .unwrap();
+        }
+
+        if buttons.pushed().a() {
+            let state = match self.selected.checked_sub(ROMS.len()) {
+                Some(index) => MyState::DataRom(self.data_roms[index].clone()),
+                None => MyState::Game(self.selected as u8),
+            };
+
             if let Some(ref mut callback) = self.on_state_change {
-                callback(MyState::Game(1));
+                callback(state);
             }
         }
     }
@@ -47,7 +89,10 @@ impl Game for MyMenu {
 impl MyMenu {
     pub fn on_enter(&mut self) {
         println!("Entered Menu state");
-        // Any other setup logic
+
+        // Again every time, so ROMs copied in while the game is open show up
+        self.data_roms = data_folder_roms();
+        self.selected = self.selected.min(ROMS.len() + self.data_roms.len() - 1);
     }
 
     pub fn set_on_state_change<F>(&mut self, callback: F)
@@ -1,70 +1,388 @@
 extern crate alloc;
 extern crate rand;
 
-use core::cmp::max;
-use core::cmp::min;
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::Range;
 
 use alloc::boxed::Box;
+use alloc::rc::Rc;
 use alloc::vec;
+use alloc::vec::Vec;
 use anyhow::Error;
-use rand::rngs::SmallRng;
-use rand::RngCore;
-use rand::SeedableRng;
+
+use crate::chip8x::ColorMap;
+use crate::font::{Font, BIG_FONT_LEN, SMALL_FONT_LEN};
+use crate::framebuffer::Framebuffer;
+use crate::megachip::{BlendMode, MegaScreen, Sample};
+use crate::random::{RandomSource, SeededRandom};
+use crate::trace::{TraceEntry, Tracer};
 
 const REGISTERS: usize = 16;
 const STACK_SIZE: usize = 16;
-const KEYS: usize = 16;
+pub const KEYS: usize = 16;
 const RAM_SIZE: usize = 65536;
+/// PC is 16 bits, so no instruction runs past this, even in the bigger Mega-Chip memory
+const PC_RANGE: usize = 1 << 16;
 const SCREEN_WIDTH: usize = 64;
 const SCREEN_HEIGHT: usize = 32;
-const SCREEN_SIZE: usize = (SCREEN_WIDTH * 2) * (SCREEN_HEIGHT * 2);
-const ENTRY_POINT: usize = 512;
+pub const ENTRY_POINT: usize = 512;
 const DEFAULT_TICK_RATE: u16 = 10;
-const BIG_FONT_ADDRESS: usize = 0x50;
-
-// Chip-8
-const FONT: [u8; 5 * 16] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, //0
-    0x20, 0x60, 0x20, 0x20, 0x70, //1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, //2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, //3
-    0x90, 0x90, 0xF0, 0x10, 0x10, //4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, //5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, //6
-    0xF0, 0x10, 0x20, 0x40, 0x40, //7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, //8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, //9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, //A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, //B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, //C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, //D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, //E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, //F
-];
-
-// Super-Chip and variants
-const BIG_FONT: [u8; 10 * 16] = [
-    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
-    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
-    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
-    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
-    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
-    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
-    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
-    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
-    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
-    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
-    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
-    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
-    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
-    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
-    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
-    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
-];
+// Same as the default tick rate at 60 fps
+const DEFAULT_SPEED: u32 = 600;
+const TIMER_HZ: u64 = 60;
+const MICROS_PER_SECOND: u64 = 1_000_000;
+// Longer gaps, like the system menu being open, are not caught up on
+const MAX_ELAPSED_MICROS: u32 = 250_000;
+// 1802 machine cycles in a 60 Hz frame (1.7609 MHz, 8 clocks each), minus the display interrupt
+// and the DMA that fetches the 32 lines of the framebuffer four times each
+const VIP_CYCLES_PER_FRAME: i32 = 3668 - 1024 - 46;
+// The VIP interpreter pushes return addresses down from 0xECF
+const VIP_STACK_TOP: usize = 0xED0;
+// Hi-res ROMs jump over the interpreter patch at 0x260 to their own code
+const HI_RES_PATCH: u16 = 0x260;
+pub const HI_RES_ENTRY_POINT: u16 = 0x2C0;
+
+/// Interpreter flavour a ROM was written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    Chip8,
+    /// VIP with the two-page 64x64 display, for ROMs starting with 1260
+    Chip8HiRes,
+    /// VIP with the VP-590 color board and a second keypad, loaded at 0x300
+    Chip8X,
+    #[default]
+    SuperChip,
+    XoChip,
+    /// Super-Chip with a 256x192 color screen, switched on by 0011
+    MegaChip,
+}
+
+impl Variant {
+    /// Addressable memory on the original interpreter
+    #[must_use]
+    pub const fn memory_size(self) -> usize {
+        match self {
+            Self::Chip8 | Self::Chip8HiRes | Self::Chip8X | Self::SuperChip => 0x1000,
+            Self::XoChip => RAM_SIZE,
+            Self::MegaChip => 1 << 24,
+        }
+    }
+
+    /// Picks the hi-res variant for Chip-8 ROMs that start by jumping to the hi-res patch
+    #[must_use]
+    pub fn detect(self, rom: &[u8]) -> Self {
+        match self {
+            Self::Chip8 if rom.starts_with(&(0x1000 | HI_RES_PATCH).to_be_bytes()) => {
+                Self::Chip8HiRes
+            }
+            _ => self,
+        }
+    }
+}
+
+/// Layout of memory on a machine, fixed when the `Chip8` is built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// Where ROMs are loaded and start running
+    pub entry_point: usize,
+    /// RAM allocated, rounded up to a power of two
+    pub ram_size: usize,
+    /// Used by the interpreter above the program, ROMs can't be loaded over it
+    pub reserved: Range<usize>,
+}
+
+impl MemoryMap {
+    /// 64 KiB from 0x200, enough for every variant
+    pub const DEFAULT: Self = Self::new(ENTRY_POINT, RAM_SIZE);
+
+    /// ETI-660, with 4 KiB and programs at 0x600
+    pub const ETI_660: Self = Self::new(0x600, 0x1000);
+
+    #[must_use]
+    pub const fn new(entry_point: usize, ram_size: usize) -> Self {
+        Self {
+            entry_point,
+            ram_size,
+            reserved: 0..0,
+        }
+    }
+
+    #[must_use]
+    pub const fn reserved(mut self, reserved: Range<usize>) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// The memory of the original machine for the variant
+    #[must_use]
+    pub const fn for_variant(variant: Variant) -> Self {
+        match variant {
+            // The VIP interpreter keeps its stack, variables and display in the top 352 bytes
+            Variant::Chip8 | Variant::Chip8HiRes => {
+                Self::new(ENTRY_POINT, 0x1000).reserved(0xEA0..0x1000)
+            }
+            // The bigger interpreter takes the page above the usual entry point
+            Variant::Chip8X => Self::new(0x300, 0x1000).reserved(0xEA0..0x1000),
+            Variant::SuperChip => Self::new(ENTRY_POINT, 0x1000),
+            // Mega-Chip memory grows past this for bigger ROMs
+            Variant::XoChip | Variant::MegaChip => Self::DEFAULT,
+        }
+    }
+
+    /// First address after the room for a program
+    fn program_end(&self) -> usize {
+        if self.reserved.is_empty() || self.reserved.start < self.entry_point {
+            self.ram_size
+        } else {
+            self.reserved.start.min(self.ram_size)
+        }
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// What an instruction reaching past the end of memory does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryPolicy {
+    /// Addresses wrap around at the memory size of the variant, like the original address lines
+    #[default]
+    Wrap,
+    /// The instruction fails without touching memory or registers
+    Error,
+    /// Execution stops on the instruction until `resume`, so it can be inspected
+    Trap,
+}
+
+/// What DXY0 draws in lo-res, Super-Chip hi-res always draws 16x16
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BigSprite {
+    /// Nothing, the VIP draws N rows and N is 0
+    Empty,
+    /// 16 rows of 8 pixels, like Super-Chip 1.1
+    Tall,
+    /// 16x16, like Octo and XO-Chip
+    #[default]
+    Full,
+}
+
+/// What happens to the part of a sprite past the screen edges, its position always wraps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpriteEdges {
+    /// It comes back on the other side, like Octo and XO-Chip
+    #[default]
+    Wrap,
+    /// It's cut off, like the VIP and Super-Chip
+    Clip,
+}
+
+/// Behaviours that differ between interpreters
+///
+/// The default has every quirk off and the full stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// DXYN in lo-res waits for the next frame, like the VIP waiting for the display interrupt
+    pub display_wait: bool,
+    pub memory: MemoryPolicy,
+    /// Nested calls allowed, up to 16
+    pub stack_depth: usize,
+    /// Keeps return addresses in RAM below 0xED0 like the VIP, for ROMs that read them
+    pub stack_in_memory: bool,
+    pub big_sprite: BigSprite,
+    pub sprite_edges: SpriteEdges,
+    /// In hi-res, VF counts the sprite rows that collided or fell off the bottom, like Super-Chip
+    /// 1.1, instead of being 0 or 1
+    pub collision_rows: bool,
+}
+
+impl Quirks {
+    #[must_use]
+    pub const fn for_variant(variant: Variant) -> Self {
+        Self {
+            display_wait: matches!(
+                variant,
+                Variant::Chip8 | Variant::Chip8HiRes | Variant::Chip8X
+            ),
+            // The VIP has 12 address lines and Octo masks to 64 KiB. Super-Chip reads on into the
+            // rest of the HP48's memory, which can't be emulated, and Mega-Chip only gets as much
+            // as was allocated for it
+            memory: match variant {
+                Variant::Chip8 | Variant::Chip8HiRes | Variant::Chip8X | Variant::XoChip => {
+                    MemoryPolicy::Wrap
+                }
+                Variant::SuperChip | Variant::MegaChip => MemoryPolicy::Error,
+            },
+            stack_depth: match variant {
+                Variant::Chip8 | Variant::Chip8HiRes | Variant::Chip8X => 12,
+                Variant::SuperChip | Variant::XoChip | Variant::MegaChip => STACK_SIZE,
+            },
+            stack_in_memory: false,
+            big_sprite: match variant {
+                Variant::Chip8 | Variant::Chip8HiRes | Variant::Chip8X => BigSprite::Empty,
+                Variant::SuperChip | Variant::MegaChip => BigSprite::Tall,
+                Variant::XoChip => BigSprite::Full,
+            },
+            sprite_edges: if matches!(variant, Variant::XoChip) {
+                SpriteEdges::Wrap
+            } else {
+                SpriteEdges::Clip
+            },
+            collision_rows: matches!(variant, Variant::SuperChip | Variant::MegaChip),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            display_wait: false,
+            memory: MemoryPolicy::default(),
+            stack_depth: STACK_SIZE,
+            stack_in_memory: false,
+            big_sprite: BigSprite::default(),
+            sprite_edges: SpriteEdges::default(),
+            collision_rows: false,
+        }
+    }
+}
+
+/// CALL or RET that the stack can't take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    /// CALL with every level of the stack in use
+    Overflow { pc: u16, depth: usize },
+    /// RET with nothing on the stack
+    Underflow { pc: u16 },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow { pc, depth } => {
+                write!(f, "Stack overflow past {depth} calls at {pc:#06X}")
+            }
+            Self::Underflow { pc } => write!(f, "Stack underflow at {pc:#06X}"),
+        }
+    }
+}
+
+impl core::error::Error for StackError {}
+
+/// Out of range memory access that stopped execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryTrap {
+    /// Instruction that made the access
+    pub pc: u16,
+    /// First address of the access
+    pub address: usize,
+    pub len: usize,
+}
+
+/// How many instructions run in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// `tick_rate` instructions per `update`, or `speed` per second in `run_for`
+    #[default]
+    Fixed,
+    /// Each instruction costs what it took the COSMAC VIP interpreter, and a frame runs as many
+    /// instructions as the 1802 would fit in it
+    Vip,
+}
 
+/// An opcode with its operands pulled out, so running it again doesn't decode it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Scd(u8),
+    Scu(u8),
+    Ret,
+    Cls,
+    Scr,
+    Scl,
+    Exit,
+    Low,
+    High,
+    Jp(u16),
+    Call(u16),
+    SeVxByte(u8, u8),
+    SneVxByte(u8, u8),
+    SaveVxVy,
+    LoadVxVy,
+    SeVxVy(u8, u8),
+    LdVxByte(u8, u8),
+    AddVxByte(u8, u8),
+    LdVxVy(u8, u8),
+    OrVxVy(u8, u8),
+    AndVxVy(u8, u8),
+    XorVxVy(u8, u8),
+    AddVxVy(u8, u8),
+    SubVxVy(u8, u8),
+    ShrVx(u8),
+    SubnVxVy(u8, u8),
+    ShlVx(u8),
+    SneVxVy(u8, u8),
+    LdIAddr(u16),
+    JpV0Addr(u16),
+    RndVxByte(u8, u8),
+    Drw(u8, u8, u8),
+    SkpVx(u8),
+    SknpVx(u8),
+    Plane,
+    LdVxDt(u8),
+    LdVxK(u8),
+    LdDtVx(u8),
+    LdStVx(u8),
+    AddIVx(u8),
+    LdFVx(u8),
+    LdHfVx(u8),
+    LdBVx(u8),
+    LdIVx(u8),
+    LdRVx,
+    LdVxR,
+    LdVxI(u8),
+    Audio,
+    LdILong,
+    Pitch,
+    Background,
+    ColorZones(u8, u8),
+    ColorRows(u8, u8, u8),
+    AddColors(u8, u8),
+    SkpVx2(u8),
+    SknpVx2(u8),
+    OutVx(u8),
+    InVx(u8),
+    MegaOff,
+    MegaOn,
+    LdHi(u8),
+    LdPal(u8),
+    SprW(u8),
+    SprH(u8),
+    Alpha(u8),
+    DigiSnd(u8),
+    StopSnd,
+    BMode(u8),
+    CCol(u8),
+    Unknown(u16),
+}
+
+/// A loop that can't change anything until a timer ticks or a key changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IdleLoop {
+    instructions: u64,
+    vip_cycles: i32,
+    // FX07 in the loop leaves the delay timer in VX
+    delay_register: Option<u8>,
+}
+
+/// A clone is a save state, random source included
 // #[derive(Debug)]
+#[derive(Clone)]
 pub struct Chip8 {
-    i: u16,
+    // 24 bits on Mega-Chip, 16 otherwise
+    i: u32,
     sp: u8,
     stack: [u16; STACK_SIZE],
     v: [u8; REGISTERS],
@@ -72,78 +390,376 @@ pub struct Chip8 {
     dt: u8,
     st: u8,
     pub keys: [bool; KEYS],
+    /// Keypad 2 of CHIP-8X
+    pub second_keys: [bool; KEYS],
 
     // RAM
     memory: Box<[u8]>,
-    pub gfx_buffer: Box<[bool]>,
+    pub gfx_buffer: Framebuffer,
+    /// Foreground and background colors of CHIP-8X
+    pub color_map: ColorMap,
+    font: Font,
+    // Replaces gfx_buffer while Mega-Chip mode is on
+    mega: Option<Box<MegaScreen>>,
 
     // Needed for the emulator
-    rnd_seed: Option<SmallRng>,
+    variant: Variant,
+    quirks: Quirks,
+    // Set by DXYN when the display wait quirk stops the frame
+    waiting_for_vblank: bool,
+    random: Box<dyn RandomSource>,
+    // Instructions fetched since the last CXNN, for random sources that follow the VIP
+    fetches_since_random: u64,
     tick_rate: u16,
+    // Instructions per second for run_for
+    speed: u32,
+    timing: Timing,
+    // Machine cycles left in the current frame, negative when the last instruction overran it
+    vip_cycles: i32,
+    // Both in microseconds times TIMER_HZ, so a timer tick is exactly MICROS_PER_SECOND
+    timer_budget: u64,
+    cycle_budget: u64,
     pub should_draw: bool,
     hi_res: bool,
-
-    pub scroll_x: i8,
-
-    pub rows_start: u8,
-    pub rows_end: u8,
+    // Decoded instructions by address, None until the first time they run
+    instruction_cache: Option<Box<[Option<Instruction>]>>,
+    idle_skip: bool,
+    skipped_cycles: u64,
+    trap: Option<MemoryTrap>,
+    // Last ROM loaded, for warm resets
+    rom: Box<[u8]>,
+    // CHIP-8X expansion port, FXF8 writes it and FXFB waits for a byte on it
+    port_output: u8,
+    port_input: Option<u8>,
+    memory_map: MemoryMap,
+    // Shared with clones, so save states keep writing to the same trace
+    tracer: Option<Rc<RefCell<dyn Tracer>>>,
 }
 
 impl Chip8 {
+    /// 64 KiB of memory, see `with_memory_map` to use less
     #[must_use]
     pub fn new() -> Self {
+        Self::with_memory_map(MemoryMap::DEFAULT)
+    }
+
+    /// Allocates memory for `memory_map`, which stays for the life of the machine
+    #[must_use]
+    pub fn with_memory_map(memory_map: MemoryMap) -> Self {
         Self {
             i: 0,
             sp: 0,
             stack: [0; STACK_SIZE],
             v: [0; REGISTERS],
-            pc: ENTRY_POINT as u16,
+            pc: memory_map.entry_point as u16,
             dt: 0,
             st: 0,
             keys: [false; KEYS],
-            memory: vec![0; RAM_SIZE].into_boxed_slice(),
-            gfx_buffer: vec![false; SCREEN_SIZE].into_boxed_slice(),
-            rnd_seed: None,
+            second_keys: [false; KEYS],
+            memory: vec![0; memory_map.ram_size.next_power_of_two()].into_boxed_slice(),
+            gfx_buffer: Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            color_map: ColorMap::new(),
+            font: Font::default(),
+            mega: None,
+            variant: Variant::default(),
+            quirks: Quirks::for_variant(Variant::default()),
+            waiting_for_vblank: false,
+            random: Box::new(SeededRandom::default()),
+            fetches_since_random: 0,
             tick_rate: DEFAULT_TICK_RATE,
+            speed: DEFAULT_SPEED,
+            timing: Timing::default(),
+            vip_cycles: 0,
+            timer_budget: 0,
+            cycle_budget: 0,
             should_draw: false,
             hi_res: false,
-            scroll_x: 0,
-            rows_start: 0,
-            rows_end: 0,
+            instruction_cache: None,
+            idle_skip: true,
+            skipped_cycles: 0,
+            trap: None,
+            rom: Box::default(),
+            port_output: 0,
+            port_input: None,
+            memory_map,
+            tracer: None,
         }
     }
 
-    pub fn reset(&mut self) {
-        self.pc = ENTRY_POINT as u16;
-        self.sp = 0;
-        self.memory = vec![0; RAM_SIZE].into_boxed_slice();
-        self.gfx_buffer = vec![false; SCREEN_SIZE].into_boxed_slice();
+    /// Puts the machine in its power-on state, with empty memory
+    ///
+    /// Settings like the variant, quirks and speed are kept, and so is the last ROM for
+    /// `warm_reset`.
+    pub fn cold_reset(&mut self) {
+        let instruction_cache = self.has_instruction_cache();
+
+        *self = Self {
+            variant: self.variant,
+            quirks: self.quirks,
+            font: self.font.clone(),
+            random: self.random.clone(),
+            tick_rate: self.tick_rate,
+            speed: self.speed,
+            timing: self.timing,
+            idle_skip: self.idle_skip,
+            rom: core::mem::take(&mut self.rom),
+            tracer: self.tracer.take(),
+            ..Self::with_memory_map(self.memory_map.clone())
+        };
+
+        self.set_instruction_cache(instruction_cache);
     }
 
-    pub fn load_rom(&mut self, rom_buf: &[u8], tick_rate: Option<u16>) {
-        // Load rom at address 0x200
-        self.memory[ENTRY_POINT..(rom_buf.len() + ENTRY_POINT)].copy_from_slice(rom_buf);
+    /// Restarts the last ROM from a cold machine, with the fonts loaded again
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no ROM was loaded yet
+    pub fn warm_reset(&mut self) -> Result<(), Error> {
+        if self.rom.is_empty() {
+            return Err(anyhow::anyhow!("No ROM to restart"));
+        }
 
-        // Load font at address 0x000
-        self.memory[0..FONT.len()].copy_from_slice(&FONT);
+        self.cold_reset();
+        self.load_memory();
+        Ok(())
+    }
 
-        // Load big font at address 0x050
-        self.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+    /// Also resets the quirks to the ones of the variant
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.quirks = Quirks::for_variant(variant);
+        // Variants decode some opcodes differently
+        self.invalidate_instructions(0, self.memory.len());
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn memory_map(&self) -> &MemoryMap {
+        &self.memory_map
+    }
+
+    /// Biggest ROM that fits between the entry point and the reserved region or end of memory
+    #[inline]
+    #[must_use]
+    pub fn max_rom_size(&self) -> usize {
+        let end = match self.variant {
+            // Memory grows to fit
+            Variant::MegaChip => self.variant.memory_size(),
+            _ => self
+                .memory_map
+                .program_end()
+                .min(self.variant.memory_size()),
+        };
+
+        end.saturating_sub(self.memory_map.entry_point)
+    }
+
+    /// Loads the ROM together with the fonts
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM is empty or doesn't fit in the memory of the current variant
+    pub fn load_rom(&mut self, rom_buf: &[u8], tick_rate: Option<u16>) -> Result<(), Error> {
+        if rom_buf.is_empty() {
+            return Err(anyhow::anyhow!("ROM is empty"));
+        }
+
+        if rom_buf.len() > self.max_rom_size() {
+            return Err(anyhow::anyhow!(
+                "ROM is {} bytes, but only {} fit above {:#05X} on {:?}",
+                rom_buf.len(),
+                self.max_rom_size(),
+                self.memory_map.entry_point,
+                self.variant,
+            ));
+        }
+
+        self.rom = rom_buf.into();
+        self.load_memory();
 
         // Set tick rate
         if let Some(x) = tick_rate {
             self.tick_rate = x;
         }
+
+        Ok(())
+    }
+
+    /// Copies the fonts and the last ROM into memory, and points PC at the ROM
+    ///
+    /// Memory starts at 64 KiB and grows to the next power of two for bigger Mega-Chip ROMs.
+    fn load_memory(&mut self) {
+        let entry_point = self.memory_map.entry_point;
+
+        let size = (entry_point + self.rom.len()).next_power_of_two();
+        if size > self.memory.len() {
+            self.memory = vec![0; size].into_boxed_slice();
+            if self.has_instruction_cache() {
+                self.set_instruction_cache(true);
+            }
+        }
+
+        // Load rom at the entry point, usually 0x200
+        self.memory[entry_point..(self.rom.len() + entry_point)].copy_from_slice(&self.rom);
+        self.pc = entry_point as u16;
+
+        self.load_font();
+        self.invalidate_instructions(0, self.memory.len());
+    }
+
+    /// Copies both fonts to their addresses
+    fn load_font(&mut self) {
+        let small = self.font.small_address as usize;
+        let big = self.font.big_address as usize;
+
+        self.memory[small..small + SMALL_FONT_LEN].copy_from_slice(&self.font.small);
+        self.memory[big..big + BIG_FONT_LEN].copy_from_slice(&self.font.big);
+
+        self.invalidate_instructions(small, SMALL_FONT_LEN);
+        self.invalidate_instructions(big, BIG_FONT_LEN);
+    }
+
+    /// Replaces the fonts, in memory right away and on every load after
+    ///
+    /// Kept across resets, like the variant.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fonts don't fit in the memory of the variant or overlap
+    pub fn set_font(&mut self, font: Font) -> Result<(), Error> {
+        font.check(self.memory_size())?;
+
+        self.font = font;
+        self.load_font();
+        Ok(())
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Sets how many instructions `run_for` executes per second
+    pub fn set_speed(&mut self, instructions_per_second: u32) {
+        self.speed = instructions_per_second;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.vip_cycles = 0;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Keeps every instruction decoded after its first run
+    ///
+    /// Costs 4 bytes per byte of memory PC can reach, 256 KiB at most.
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        let len = self.memory.len().min(PC_RANGE);
+        self.instruction_cache = enabled.then(|| vec![None; len].into_boxed_slice());
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn has_instruction_cache(&self) -> bool {
+        self.instruction_cache.is_some()
+    }
+
+    /// Drops the cached instructions overlapping `len` bytes of memory from `address`
+    ///
+    /// Every store to memory has to call this, or self-modifying code runs stale instructions.
+    fn invalidate_instructions(&mut self, address: usize, len: usize) {
+        if let Some(ref mut cache) = self.instruction_cache {
+            // The instruction starting on the byte before also reads the first one
+            let start = address.saturating_sub(1).min(cache.len());
+            let end = (address + len).min(cache.len());
+            cache[start..end].fill(None);
+        }
+    }
+
+    /// Fast-forwards through loops waiting on the delay timer or a key, on by default
+    ///
+    /// Whole iterations of the loop are skipped, so the machine ends up exactly where running
+    /// them would have left it.
+    pub fn set_idle_skip(&mut self, enabled: bool) {
+        self.idle_skip = enabled;
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn idle_skip(&self) -> bool {
+        self.idle_skip
+    }
+
+    /// Instructions fast-forwarded by idle skipping since the last reset
+    #[inline]
+    #[must_use]
+    pub const fn skipped_cycles(&self) -> u64 {
+        self.skipped_cycles
     }
 
     pub fn set_random_seed(&mut self, seed: u64) {
-        let small_rng = SmallRng::seed_from_u64(seed);
-        self.rnd_seed = Some(small_rng);
+        self.random = Box::new(SeededRandom::new(seed));
+    }
+
+    /// Replaces the seeded `SmallRng` CXNN uses by default
+    pub fn set_random_source(&mut self, random: Box<dyn RandomSource>) {
+        self.random = random;
+    }
+
+    /// Passes the state before every instruction to `tracer`, `None` stops tracing
+    ///
+    /// Loops fast-forwarded by idle skipping don't run, so turn it off for a complete trace.
+    pub fn set_tracer(&mut self, tracer: Option<Rc<RefCell<dyn Tracer>>>) {
+        self.tracer = tracer;
+    }
+
+    /// Registers and timers as a trace line sees them
+    #[must_use]
+    pub fn trace_entry(&self) -> TraceEntry {
+        TraceEntry {
+            pc: self.pc,
+            opcode: self.get_opcode(),
+            i: self.i,
+            v: self.v,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+        }
     }
 
     #[inline]
-    pub const fn get_opcode(&self) -> u16 {
-        (self.memory[self.pc as usize] as u16) << 8 | (self.memory[self.pc as usize + 1] as u16)
+    #[must_use]
+    pub fn get_opcode(&self) -> u16 {
+        (u16::from(self.read(self.pc as usize)) << 8) | u16::from(self.read(self.pc as usize + 1))
     }
 
     #[inline]
@@ -170,15 +786,337 @@ impl Chip8 {
         self.hi_res
     }
 
+    /// Last byte FXF8 wrote to the CHIP-8X expansion port
+    #[inline]
+    #[must_use]
+    pub const fn port_output(&self) -> u8 {
+        self.port_output
+    }
+
+    /// Hands a byte to the next FXFB, which waits until there is one
+    pub fn set_port_input(&mut self, byte: u8) {
+        self.port_input = Some(byte);
+    }
+
+    /// The color screen, while Mega-Chip mode is on
+    #[inline]
+    #[must_use]
+    pub fn mega_screen(&self) -> Option<&MegaScreen> {
+        self.mega.as_deref()
+    }
+
+    /// Mutable for `Renderer::render_mega`, which tracks the frames it has shown
+    #[inline]
+    pub fn mega_screen_mut(&mut self) -> Option<&mut MegaScreen> {
+        self.mega.as_deref_mut()
+    }
+
+    /// Sample started by 060N and its bytes, until 0700 stops it
+    ///
+    /// The bytes are cut short if the header claims more than memory holds.
+    #[must_use]
+    pub fn sample(&self) -> Option<(Sample, &[u8])> {
+        let sample = self.mega.as_ref()?.sample?;
+        let start = sample.address.min(self.memory.len());
+        let end = (sample.address + sample.len).min(self.memory.len());
+        Some((sample, &self.memory[start..end]))
+    }
+
+    /// Memory access stopped at, with the trap policy
+    #[inline]
+    #[must_use]
+    pub const fn trap(&self) -> Option<MemoryTrap> {
+        self.trap
+    }
+
+    /// Runs the trapped instruction again on the next tick
+    pub fn resume(&mut self) {
+        self.trap = None;
+    }
+
+    /// Checks `len` bytes from `address` against the memory policy before touching any of them
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the access is out of range and the policy doesn't wrap. With the trap
+    /// policy, the trap is set too.
+    fn check_access(&mut self, address: usize, len: usize) -> Result<(), Error> {
+        let size = self.memory_size();
+
+        if address + len <= size || self.quirks.memory == MemoryPolicy::Wrap {
+            return Ok(());
+        }
+
+        if self.quirks.memory == MemoryPolicy::Trap {
+            self.trap = Some(MemoryTrap {
+                pc: self.pc,
+                address,
+                len,
+            });
+        }
+
+        Err(anyhow::anyhow!(
+            "Access to {len} bytes at {address:#06X} is past {size:#06X} at {:#06X}",
+            self.pc,
+        ))
+    }
+
+    /// Memory of the variant that is actually allocated, always a power of two
+    #[inline]
+    fn memory_size(&self) -> usize {
+        self.variant.memory_size().min(self.memory.len())
+    }
+
+    #[inline]
+    fn wrap(&self, address: usize) -> usize {
+        address & (self.memory_size() - 1)
+    }
+
+    /// Mask keeping I within the address space of the variant
+    #[inline]
+    const fn i_mask(&self) -> u32 {
+        match self.variant {
+            Variant::MegaChip => 0xFF_FFFF,
+            _ => 0xFFFF,
+        }
+    }
+
+    #[inline]
+    fn read(&self, address: usize) -> u8 {
+        self.memory[self.wrap(address)]
+    }
+
+    #[inline]
+    fn write(&mut self, address: usize, value: u8) {
+        let address = self.wrap(address);
+        self.memory[address] = value;
+        self.invalidate_instructions(address, 1);
+    }
+
+    /// Return address at `level` of the stack, 0 being the outermost call
+    fn stack_entry(&self, level: usize) -> u16 {
+        if self.quirks.stack_in_memory {
+            let address = VIP_STACK_TOP - 2 * (level + 1);
+            (u16::from(self.read(address)) << 8) | u16::from(self.read(address + 1))
+        } else {
+            self.stack[level]
+        }
+    }
+
+    fn set_stack_entry(&mut self, level: usize, pc: u16) {
+        if self.quirks.stack_in_memory {
+            let address = VIP_STACK_TOP - 2 * (level + 1);
+            let [high, low] = pc.to_be_bytes();
+            self.write(address, high);
+            self.write(address + 1, low);
+        } else {
+            self.stack[level] = pc;
+        }
+    }
+
     pub fn update(&mut self) -> Result<(), Error> {
         self.should_draw = false;
+        self.waiting_for_vblank = false;
         self.update_timers();
-        for _ in 0..self.tick_rate {
+
+        if self.timing == Timing::Vip {
+            return self.run_vip_frame();
+        }
+
+        self.run_cycles(u64::from(self.tick_rate))
+    }
+
+    /// Runs the interpreter for the given wall-clock time
+    ///
+    /// Timers tick at exactly 60 Hz and instructions run at `speed` per second, whatever the frame
+    /// rate. Leftover time is carried over to the next call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an instruction fails, the rest of the time is dropped
+    pub fn run_for(&mut self, elapsed_micros: u32) -> Result<(), Error> {
+        self.should_draw = false;
+
+        let mut remaining = u64::from(elapsed_micros.min(MAX_ELAPSED_MICROS)) * TIMER_HZ;
+
+        // Run in slices that end on timer ticks, so timers change between the right instructions
+        while remaining > 0 {
+            let step = remaining.min(MICROS_PER_SECOND - self.timer_budget);
+            remaining -= step;
+
+            self.timer_budget += step;
+
+            if self.timing == Timing::Fixed {
+                self.cycle_budget += step * u64::from(self.speed);
+
+                let cycles = self.cycle_budget / (MICROS_PER_SECOND * TIMER_HZ);
+                self.cycle_budget %= MICROS_PER_SECOND * TIMER_HZ;
+
+                self.run_cycles(cycles)?;
+            }
+
+            if self.timer_budget == MICROS_PER_SECOND {
+                self.timer_budget = 0;
+                self.waiting_for_vblank = false;
+
+                if self.timing == Timing::Vip {
+                    self.run_vip_frame()?;
+                }
+
+                self.update_timers();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a 60 Hz frame worth of VIP machine cycles
+    fn run_vip_frame(&mut self) -> Result<(), Error> {
+        self.vip_cycles += VIP_CYCLES_PER_FRAME;
+
+        while self.vip_cycles > 0 && self.trap.is_none() {
+            if let Some(idle) = self.idle_loop() {
+                // Leave at least a cycle, so the last iteration runs for real if it has to
+                let iterations = (self.vip_cycles - 1) / idle.vip_cycles;
+
+                if iterations > 0 {
+                    self.vip_cycles -= iterations * idle.vip_cycles;
+                    self.skip_idle(idle, iterations.unsigned_abs().into());
+                    continue;
+                }
+            }
+
+            // Cost depends on registers, so take it before they change
+            let cycles = self.vip_cycles(self.get_opcode());
             self.tick()?;
+            self.vip_cycles -= cycles;
+
+            // Rest of the frame is spent waiting for the interrupt
+            if self.waiting_for_vblank {
+                self.waiting_for_vblank = false;
+                self.vip_cycles = 0;
+                break;
+            }
         }
+
         Ok(())
     }
 
+    /// Runs up to `cycles` instructions, stopping early when waiting for the display
+    fn run_cycles(&mut self, cycles: u64) -> Result<(), Error> {
+        let mut remaining = cycles;
+
+        // Cycles spent waiting for the display are lost
+        while remaining > 0 && !self.waiting_for_vblank && self.trap.is_none() {
+            if let Some(idle) = self.idle_loop() {
+                let iterations = remaining / idle.instructions;
+
+                if iterations > 0 {
+                    remaining -= iterations * idle.instructions;
+                    self.skip_idle(idle, iterations);
+                    continue;
+                }
+            }
+
+            self.tick()?;
+            remaining -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Recognizes the busy loops ROMs wait in at PC
+    ///
+    /// Timers and keys only change between calls to `run_for`, so inside one every iteration
+    /// of these loops does the same thing:
+    /// - `1NNN` jumping to itself
+    /// - `FX0A` with no key down
+    /// - `EX9E` or `EXA1` followed by a `1NNN` back to it, while the key stays the same
+    /// - `FX07`, `3XNN` and a `1NNN` back to the start, while the delay timer isn't NN
+    fn idle_loop(&self) -> Option<IdleLoop> {
+        if !self.idle_skip {
+            return None;
+        }
+
+        let pc = self.pc as usize;
+        let opcode_at = |address: usize| {
+            let bytes = self.memory.get(address..address + 2)?;
+            Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+        let jumps_back = |address: usize| opcode_at(address) == Some(0x1000 | self.pc);
+
+        let opcode = opcode_at(pc)?;
+        let (x, _, nnn, kk, _) = self.get_variables(opcode);
+
+        let (opcodes, delay_register) = match self.get_nibbles(opcode) {
+            (0x1, _, _, _) if nnn == self.pc => (&[opcode][..], None),
+            (0xF, _, 0x0, 0xA) if !self.keys.contains(&true) => (&[opcode][..], None),
+            (0xE, _, 0x9, 0xE) | (0xE, _, 0xA, 0x1) if jumps_back(pc + 2) => {
+                let pressed = *self.keys.get(self.v[x as usize] as usize)?;
+                // SKP waits for a press and SKNP for a release
+                if pressed == (kk == 0x9E) {
+                    return None;
+                }
+                (&[opcode, 0x1000 | self.pc][..], None)
+            }
+            (0xF, _, 0x0, 0x7) => {
+                let skip = opcode_at(pc + 2)?;
+                let skips_on_vx = skip & 0xFF00 == 0x3000 | (u16::from(x) << 8);
+                let (_, _, _, target, _) = self.get_variables(skip);
+
+                if !skips_on_vx || target == self.dt || !jumps_back(pc + 4) {
+                    return None;
+                }
+                (&[opcode, skip, 0x1000 | self.pc][..], Some(x))
+            }
+            _ => return None,
+        };
+
+        Some(IdleLoop {
+            instructions: opcodes.len() as u64,
+            vip_cycles: opcodes.iter().map(|&opcode| self.vip_cycles(opcode)).sum(),
+            delay_register,
+        })
+    }
+
+    /// Leaves the machine as if the idle loop ran `iterations` times
+    fn skip_idle(&mut self, idle: IdleLoop, iterations: u64) {
+        if let Some(x) = idle.delay_register {
+            self.v[x as usize] = self.dt;
+        }
+
+        self.skipped_cycles += iterations * idle.instructions;
+        self.fetches_since_random += iterations * idle.instructions;
+    }
+
+    /// Machine cycles the VIP interpreter spends on an instruction, fetch and decode included
+    ///
+    /// Based on published timings of the original interpreter, rounded to whole cycles.
+    fn vip_cycles(&self, opcode: u16) -> i32 {
+        let (x, _, _, _, n) = self.get_variables(opcode);
+
+        match self.get_nibbles(opcode) {
+            (0, 0, 0xE, 0x0) => 24,
+            (0x3 | 0x4 | 0xA, _, _, _) => 12,
+            (0x5 | 0x9 | 0xE, _, _, _) => 16,
+            (0x6, _, _, _) => 6,
+            (0x8, _, _, _) => 44,
+            (0xC, _, _, _) => 36,
+            (0xD, _, _, _) => {
+                // Sprites that don't start on a byte boundary get shifted into two bytes per row
+                let row = if self.v[x as usize] % 8 == 0 { 14 } else { 26 };
+                26 + i32::from(n) * row
+            }
+            (0xF, _, 0x1, 0xE) => 19,
+            (0xF, _, 0x2, 0x9) => 20,
+            (0xF, _, 0x3, 0x3) => 204,
+            (0xF, _, 0x5 | 0x6, 0x5) => 28 + 14 * (i32::from(x) + 1),
+            (0x7 | 0xF, _, _, _) => 10,
+            // Jumps and calls, 0NNN, 1NNN, 2NNN and BNNN
+            _ => 23,
+        }
+    }
+
     fn update_timers(&mut self) {
         if self.dt > 0 {
             self.dt -= 1;
@@ -190,13 +1128,7 @@ impl Chip8 {
     }
 
     pub fn full_screen_rows(&mut self) {
-        self.rows_start = 0;
-        self.rows_end = u8::MAX;
-    }
-
-    pub fn reset_rows(&mut self) {
-        self.rows_start = u8::MAX;
-        self.rows_end = 0;
+        self.gfx_buffer.mark_all_dirty();
     }
 
     #[must_use]
@@ -206,7 +1138,7 @@ impl Chip8 {
 
     #[inline]
     pub const fn width(&self) -> usize {
-        if self.is_hi_res() {
+        if self.is_hi_res() && !matches!(self.variant, Variant::Chip8HiRes) {
             128
         } else {
             64
@@ -224,19 +1156,37 @@ impl Chip8 {
 
     // Start opcodes
 
+    /// Scrolls the screen down by N rows
     fn scd(&mut self, n: u8) {
-        self.gfx_buffer.rotate_right(SCREEN_WIDTH * n as usize);
+        if let Some(ref mut mega) = self.mega {
+            mega.scroll_down(n as usize);
+        }
+        self.gfx_buffer.scroll_down(n as usize);
+        self.full_screen_rows();
+        self.should_draw = true;
         self.pc += 2;
     }
 
+    /// Scrolls the screen up by N rows
     fn scu(&mut self, n: u8) {
-        self.gfx_buffer.rotate_left(SCREEN_WIDTH * n as usize);
+        if let Some(ref mut mega) = self.mega {
+            mega.scroll_up(n as usize);
+        }
+        self.gfx_buffer.scroll_up(n as usize);
+        self.full_screen_rows();
+        self.should_draw = true;
         self.pc += 2;
     }
 
     /// Clear screen
+    ///
+    /// In Mega-Chip mode, shows the frame drawn so far first.
     fn cls(&mut self) {
-        self.gfx_buffer = vec![false; SCREEN_SIZE].into_boxed_slice();
+        if let Some(ref mut mega) = self.mega {
+            mega.present();
+            mega.clear();
+        }
+        self.gfx_buffer.clear();
         self.full_screen_rows();
         self.should_draw = true;
         self.pc += 2;
@@ -245,25 +1195,31 @@ impl Chip8 {
     /// Return from subroutine
     fn ret(&mut self) -> Result<(), Error> {
         if self.sp < 1 {
-            return Err(anyhow::anyhow!("Stack underflow"));
+            return Err(StackError::Underflow { pc: self.pc }.into());
         }
         self.sp -= 1;
-        self.pc = self.stack[self.sp as usize];
+        self.pc = self.stack_entry(self.sp as usize);
         self.pc += 2;
         Ok(())
     }
 
+    /// Scrolls the screen right by 4 pixels
     fn scr(&mut self) {
-        // self.gfx_buffer.rotate_right(4);
-        self.scroll_x += 4;
+        if let Some(ref mut mega) = self.mega {
+            mega.scroll_right(4);
+        }
+        self.gfx_buffer.scroll_right(4);
         self.full_screen_rows();
         self.should_draw = true;
         self.pc += 2;
     }
 
+    /// Scrolls the screen left by 4 pixels
     fn scl(&mut self) {
-        // self.gfx_buffer.rotate_left(4);
-        self.scroll_x -= 4;
+        if let Some(ref mut mega) = self.mega {
+            mega.scroll_left(4);
+        }
+        self.gfx_buffer.scroll_left(4);
         self.full_screen_rows();
         self.should_draw = true;
         self.pc += 2;
@@ -275,25 +1231,42 @@ impl Chip8 {
 
     fn low(&mut self) {
         self.hi_res = false;
+        self.gfx_buffer.resize(self.width(), self.height());
         self.pc += 2;
     }
 
     fn high(&mut self) {
         self.hi_res = true;
+        self.gfx_buffer.resize(self.width(), self.height());
         self.pc += 2;
     }
 
     /// Jumps to address NNN
+    ///
+    /// On the hi-res variant, 1260 at the entry point runs the 1802 patch that sets up the 64x64
+    /// display. The patch is stood in for, and the ROM goes on from its own code at 0x2C0.
     fn jp_addr(&mut self, nnn: u16) {
+        if self.variant == Variant::Chip8HiRes
+            && usize::from(self.pc) == self.memory_map.entry_point
+            && nnn == HI_RES_PATCH
+        {
+            self.hi_res = true;
+            self.gfx_buffer.resize(self.width(), self.height());
+            self.pc = HI_RES_ENTRY_POINT;
+            return;
+        }
+
         self.pc = nnn;
     }
 
     /// Calls subroutine at NNN
     fn call_addr(&mut self, nnn: u16) -> Result<(), Error> {
-        if self.sp >= (size_of::<u16>() * STACK_SIZE) as u8 {
-            return Err(anyhow::anyhow!("Stack overflow"));
+        let depth = self.quirks.stack_depth.min(STACK_SIZE);
+
+        if self.sp as usize >= depth {
+            return Err(StackError::Overflow { pc: self.pc, depth }.into());
         }
-        self.stack[self.sp as usize] = self.pc;
+        self.set_stack_entry(self.sp as usize, self.pc);
         self.sp += 1;
         self.pc = nnn;
         Ok(())
@@ -434,7 +1407,7 @@ impl Chip8 {
 
     /// Sets I to the address NNN.
     fn ld_i_addr(&mut self, nnn: u16) {
-        self.i = nnn;
+        self.i = u32::from(nnn);
         self.pc += 2;
     }
 
@@ -446,64 +1419,123 @@ impl Chip8 {
 
     /// Sets VX to a random number, masked by NN.
     fn rnd_vx_byte(&mut self, x: u8, kk: u8) {
-        match self.rnd_seed {
-            Some(ref mut seed) => {
-                self.v[x as usize] = (seed.next_u32() as u8) & kk;
-            }
-            None => self.v[x as usize] = 1 & kk,
-        }
-
+        self.random
+            .fetched(core::mem::take(&mut self.fetches_since_random));
+        self.v[x as usize] = self.random.next_byte() & kk;
         self.pc += 2;
     }
 
     /// Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels.
     /// Shamelessly stolen from https://github.com/machinetech/chip8 until I figure out how it works
-    fn drw_vx_vy_nibble(&mut self, x: u8, y: u8, n: u8) {
+    fn drw_vx_vy_nibble(&mut self, x: u8, y: u8, n: u8) -> Result<(), Error> {
         let gfx_start_x = self.v[x as usize] as usize;
         let gfx_start_y = self.v[y as usize] as usize;
 
+        if let Some(ref mega) = self.mega {
+            let len = mega.sprite_width * mega.sprite_height;
+            return self.drw_mega(gfx_start_x, gfx_start_y, len);
+        }
+
         // Determine sprite dimensions
-        let sprite_width = if n == 0 { 16 } else { 8 };
-        let sprite_height = if n == 0 { 16 } else { n as usize };
+        let (sprite_width, sprite_height) = self.sprite_size(n);
 
-        self.v[0x0F] = 0; // Clear the collision flag
+        self.check_access(self.i as usize, sprite_height * sprite_width / 8)?;
+
+        // The start position always wraps, only the rest of the sprite can fall off
+        let gfx_start_y = gfx_start_y % self.height();
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
 
         for y_offset in 0..sprite_height {
+            let mut gfx_y = gfx_start_y + y_offset;
+            if gfx_y >= self.height() {
+                if self.quirks.sprite_edges == SpriteEdges::Clip {
+                    clipped_rows += 1;
+                    continue;
+                }
+                gfx_y -= self.height();
+            }
+
             let sprite_memory_index = self.i as usize + y_offset * (sprite_width / 8);
 
             // Retrieve the sprite row
             let row_bits = if sprite_width == 16 {
-                let high_byte = self.memory[sprite_memory_index] as u16;
-                let low_byte = self.memory[sprite_memory_index + 1] as u16;
+                let high_byte = u16::from(self.read(sprite_memory_index));
+                let low_byte = u16::from(self.read(sprite_memory_index + 1));
                 (high_byte << 8) | low_byte
             } else {
-                self.memory[sprite_memory_index] as u16
+                u16::from(self.read(sprite_memory_index))
             };
 
-            for x_offset in 0..sprite_width {
-                let gfx_x = (gfx_start_x + x_offset) % self.width();
-                let gfx_y = (gfx_start_y + y_offset) % self.height();
-                let pixel_mask = 1 << (sprite_width - 1 - x_offset);
-                let sprite_pixel = (row_bits & pixel_mask) != 0;
+            if row_bits == 0 {
+                continue;
+            }
 
-                if sprite_pixel {
-                    let gfx_index = gfx_x + gfx_y * self.width();
-                    let was_pixel_set = self.gfx_buffer[gfx_index];
-                    self.gfx_buffer[gfx_index] ^= true;
+            // The whole row is drawn and checked for collisions at once
+            let collision = match self.quirks.sprite_edges {
+                SpriteEdges::Wrap => {
+                    self.gfx_buffer.xor_row(gfx_start_x, gfx_y, row_bits, sprite_width)
+                }
+                SpriteEdges::Clip => {
+                    self.gfx_buffer.xor_row_clipped(gfx_start_x, gfx_y, row_bits, sprite_width)
+                }
+            };
+            if collision {
+                collided_rows += 1;
+            }
 
-                    if was_pixel_set && !self.gfx_buffer[gfx_index] {
-                        self.v[0x0F] = 1; // Collision detected
-                    }
+            self.should_draw = true;
+        }
 
-                    self.rows_start = min(self.rows_start, gfx_y as u8);
-                    self.rows_end = max(self.rows_end, (gfx_y + sprite_height) as u8);
+        self.v[0x0F] = if self.quirks.collision_rows && self.hi_res {
+            collided_rows + clipped_rows
+        } else {
+            u8::from(collided_rows > 0)
+        };
 
-                    self.should_draw = true;
-                }
-            }
+        // Only Super-Chip hi-res draws right away, the VIP always waits
+        if self.quirks.display_wait && (!self.hi_res || self.variant == Variant::Chip8HiRes) {
+            self.waiting_for_vblank = true;
         }
 
         self.pc += 2; // Increment the program counter
+        Ok(())
+    }
+
+    /// Width and height of the sprite DXYN draws
+    const fn sprite_size(&self, n: u8) -> (usize, usize) {
+        if n > 0 {
+            return (8, n as usize);
+        }
+
+        // The 64x64 VIP interpreter has no big sprites either
+        if self.hi_res && !matches!(self.variant, Variant::Chip8HiRes) {
+            return (16, 16);
+        }
+
+        match self.quirks.big_sprite {
+            BigSprite::Empty => (8, 0),
+            BigSprite::Tall => (8, 16),
+            BigSprite::Full => (16, 16),
+        }
+    }
+
+    /// Draws a SPRW by SPRH sprite of palette indices at (VX, VY), N is ignored
+    fn drw_mega(&mut self, x: usize, y: usize, len: usize) -> Result<(), Error> {
+        self.check_access(self.i as usize, len)?;
+
+        let i = self.i as usize;
+        let mask = self.memory_size() - 1;
+
+        if let Some(ref mut mega) = self.mega {
+            let memory = &self.memory;
+            let collision = mega.draw_sprite(x, y, |offset| memory[(i + offset) & mask]);
+            self.v[0x0F] = u8::from(collision);
+        }
+
+        self.should_draw = true;
+        self.pc += 2;
+        Ok(())
     }
 
     /// Skips the next instruction if the key stored in VX is pressed.
@@ -563,37 +1595,44 @@ impl Chip8 {
 
     /// Adds VX to I
     fn add_i_vx(&mut self, x: u8) {
-        self.i += self.v[x as usize] as u16;
+        // I is 16 bits, 24 on Mega-Chip, accesses through it wrap or fault on their own
+        self.i = (self.i + u32::from(self.v[x as usize])) & self.i_mask();
         self.pc += 2;
     }
 
     /// Sets I to the location of the sprite for the character in VX.
     fn ld_f_vx(&mut self, x: u8) {
-        self.i = self.v[x as usize] as u16 * 5;
+        self.i = u32::from(self.font.small_glyph(self.v[x as usize]));
         self.pc += 2;
     }
 
     fn ld_hf_vx(&mut self, x: u8) {
-        self.i = BIG_FONT_ADDRESS as u16 + self.v[x as usize] as u16 * 10;
+        self.i = u32::from(self.font.big_glyph(self.v[x as usize]));
         self.pc += 2;
     }
 
     /// Stores the Binary-coded decimal representation of VX at the addresses I, I plus 1, and I plus 2
-    fn ld_b_vx(&mut self, x: u8) {
-        self.memory[self.i as usize] = self.v[x as usize] / 100;
-        self.memory[self.i as usize + 1] = (self.v[x as usize] / 10) % 10;
-        self.memory[self.i as usize + 2] = self.v[x as usize] % 10;
+    fn ld_b_vx(&mut self, x: u8) -> Result<(), Error> {
+        self.check_access(self.i as usize, 3)?;
+
+        self.write(self.i as usize, self.v[x as usize] / 100);
+        self.write(self.i as usize + 1, (self.v[x as usize] / 10) % 10);
+        self.write(self.i as usize + 2, self.v[x as usize] % 10);
         self.pc += 2;
+        Ok(())
     }
 
     /// Stores V0 to VX in memory starting at address I
-    fn ld_i_vx(&mut self, x: u8) {
+    fn ld_i_vx(&mut self, x: u8) -> Result<(), Error> {
+        self.check_access(self.i as usize, x as usize + 1)?;
+
         (0..=x).for_each(|i| {
-            self.memory[self.i as usize + i as usize] = self.v[i as usize];
+            self.write(self.i as usize + i as usize, self.v[i as usize]);
         });
 
-        self.i += x as u16 + 1;
+        self.i = (self.i + u32::from(x) + 1) & self.i_mask();
         self.pc += 2;
+        Ok(())
     }
 
     fn ld_r_vx(&mut self) {
@@ -604,83 +1643,450 @@ impl Chip8 {
         unimplemented!();
     }
 
-    fn ld_vx_i(&mut self, x: u8) {
+    fn ld_vx_i(&mut self, x: u8) -> Result<(), Error> {
+        self.check_access(self.i as usize, x as usize + 1)?;
+
         (0..=x).for_each(|i| {
-            self.v[i as usize] = self.memory[self.i as usize + i as usize];
+            self.v[i as usize] = self.read(self.i as usize + i as usize);
         });
 
-        self.i += x as u16 + 1;
+        self.i = (self.i + u32::from(x) + 1) & self.i_mask();
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Steps the background through blue, black, green and red
+    fn background(&mut self) {
+        self.color_map.cycle_background();
+        self.gfx_buffer.mark_all_dirty();
+        self.should_draw = true;
+        self.pc += 2;
+    }
+
+    /// Colors zones of 8x4 pixels with V(X+1)
+    ///
+    /// The low nibbles of VX and VY are the first zone across and down, the high nibbles how many
+    /// more zones to color.
+    fn color_zones(&mut self, x: u8, y: u8) {
+        let vx = self.v[x as usize] as usize;
+        let vy = self.v[y as usize] as usize;
+        let color = self.v[(x as usize + 1) % REGISTERS];
+
+        self.color_map.fill(
+            vx & 0xF,
+            (vy & 0xF) * 4,
+            (vx >> 4) + 1,
+            ((vy >> 4) + 1) * 4,
+            color,
+        );
+        self.gfx_buffer.mark_all_dirty();
+        self.should_draw = true;
+        self.pc += 2;
+    }
+
+    /// Colors N rows of the 8 pixel wide zone at (VX, VY) with V(X+1)
+    fn color_rows(&mut self, x: u8, y: u8, n: u8) {
+        let vx = self.v[x as usize] as usize;
+        let vy = self.v[y as usize] as usize;
+        let color = self.v[(x as usize + 1) % REGISTERS];
+
+        self.color_map.fill(vx / 8, vy, 1, n as usize, color);
+        self.gfx_buffer.mark_all_dirty();
+        self.should_draw = true;
+        self.pc += 2;
+    }
+
+    /// Adds VY to VX one nibble at a time, each a color from 0 to 7 that wraps without carrying
+    fn add_colors(&mut self, x: u8, y: u8) {
+        let sum = (self.v[x as usize] & 0x77) + (self.v[y as usize] & 0x77);
+        self.v[x as usize] = sum & 0x77;
+        self.pc += 2;
+    }
+
+    /// Skips the next instruction if the key stored in VX is pressed on keypad 2
+    fn skp_vx_2(&mut self, x: u8) {
+        if self.second_keys[self.v[x as usize] as usize % KEYS] {
+            self.pc += 2;
+        }
+        self.pc += 2;
+    }
+
+    /// Skips the next instruction if the key stored in VX isn't pressed on keypad 2
+    fn sknp_vx_2(&mut self, x: u8) {
+        if !self.second_keys[self.v[x as usize] as usize % KEYS] {
+            self.pc += 2;
+        }
+        self.pc += 2;
+    }
+
+    /// Writes VX to the expansion port
+    fn out_vx(&mut self, x: u8) {
+        self.port_output = self.v[x as usize];
+        self.pc += 2;
+    }
+
+    /// Waits for a byte on the expansion port and stores it in VX
+    fn in_vx(&mut self, x: u8) {
+        if let Some(byte) = self.port_input.take() {
+            self.v[x as usize] = byte;
+            self.pc += 2;
+        }
+    }
+
+    /// Leaves Mega-Chip mode for the Super-Chip screen
+    fn mega_off(&mut self) {
+        self.mega = None;
+        self.gfx_buffer.mark_all_dirty();
+        self.should_draw = true;
+        self.pc += 2;
+    }
+
+    /// Switches to the 256x192 color screen
+    fn mega_on(&mut self) {
+        self.mega = Some(Box::default());
+        self.should_draw = true;
+        self.pc += 2;
+    }
+
+    /// Loads I with NN and the 16 bits after the instruction, 01NN NNNN
+    fn ld_hi(&mut self, nn: u8) {
+        let pc = self.pc as usize;
+        self.i = u32::from_be_bytes([0, nn, self.read(pc + 2), self.read(pc + 3)]);
+        self.pc += 4;
+    }
+
+    /// Loads NN ARGB colors from I into the palette, from index 1 on
+    fn ld_pal(&mut self, nn: u8) -> Result<(), Error> {
+        let len = nn as usize * 4;
+        self.check_access(self.i as usize, len)?;
+
+        let colors: Vec<u8> = (0..len)
+            .map(|offset| self.read(self.i as usize + offset))
+            .collect();
+        if let Some(ref mut mega) = self.mega {
+            mega.load_palette(&colors);
+        }
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Sets the sprite width, 0 is 256
+    fn spr_w(&mut self, nn: u8) {
+        if let Some(ref mut mega) = self.mega {
+            mega.sprite_width = if nn == 0 { 256 } else { nn as usize };
+        }
+        self.pc += 2;
+    }
+
+    /// Sets the sprite height, 0 is 256
+    fn spr_h(&mut self, nn: u8) {
+        if let Some(ref mut mega) = self.mega {
+            mega.sprite_height = if nn == 0 { 256 } else { nn as usize };
+        }
+        self.pc += 2;
+    }
+
+    /// Sets the alpha of the whole screen
+    fn alpha(&mut self, nn: u8) {
+        if let Some(ref mut mega) = self.mega {
+            mega.alpha = nn;
+        }
+        self.pc += 2;
+    }
+
+    /// Plays the sample at I, once or looping when N is 0
+    ///
+    /// The header is the rate in samples per second (2 bytes), the length (3 bytes) and a zero
+    /// byte, followed by 8-bit unsigned samples.
+    fn digi_snd(&mut self, n: u8) -> Result<(), Error> {
+        self.check_access(self.i as usize, 6)?;
+
+        let header: [u8; 6] = core::array::from_fn(|offset| self.read(self.i as usize + offset));
+        let sample = Sample {
+            address: self.wrap(self.i as usize + 6),
+            rate: u16::from_be_bytes([header[0], header[1]]),
+            len: u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize,
+            looping: n == 0,
+        };
+
+        if let Some(ref mut mega) = self.mega {
+            mega.sample = Some(sample);
+        }
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn stop_snd(&mut self) {
+        if let Some(ref mut mega) = self.mega {
+            mega.sample = None;
+        }
+        self.pc += 2;
+    }
+
+    /// Sets how sprites blend with the screen
+    fn b_mode(&mut self, n: u8) -> Result<(), Error> {
+        let blend = BlendMode::from_nibble(n)
+            .ok_or_else(|| anyhow::anyhow!("Unknown blend mode {n} at {:#06X}", self.pc))?;
+
+        if let Some(ref mut mega) = self.mega {
+            mega.blend = blend;
+        }
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Sets the palette index that DXYN reports collisions with
+    fn c_col(&mut self, nn: u8) {
+        if let Some(ref mut mega) = self.mega {
+            mega.collision_color = Some(nn);
+        }
         self.pc += 2;
     }
 
     // End opcodes
 
-    fn tick(&mut self) -> Result<(), Error> {
-        let opcode = self.get_opcode();
+    /// Opcodes that CHIP-8X adds or takes over, BNNN among them
+    fn decode_chip8x(&self, opcode: u16) -> Option<Instruction> {
+        let (x, y, _, _, n) = self.get_variables(opcode);
+
+        let instruction = match self.get_nibbles(opcode) {
+            (0, 0x2, 0xA, 0x0) => Instruction::Background,
+            (0x5, _, _, 0x1) => Instruction::AddColors(x, y),
+            (0xB, _, _, 0) => Instruction::ColorZones(x, y),
+            (0xB, _, _, _) => Instruction::ColorRows(x, y, n),
+            (0xE, _, 0xF, 0x2) => Instruction::SkpVx2(x),
+            (0xE, _, 0xF, 0x5) => Instruction::SknpVx2(x),
+            (0xF, _, 0xF, 0x8) => Instruction::OutVx(x),
+            (0xF, _, 0xF, 0xB) => Instruction::InVx(x),
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// Opcodes that Mega-Chip adds in the 0NNN range
+    fn decode_megachip(&self, opcode: u16) -> Option<Instruction> {
+        let (_, _, _, kk, n) = self.get_variables(opcode);
+
+        let instruction = match self.get_nibbles(opcode) {
+            (0, 0, 0x1, 0x0) => Instruction::MegaOff,
+            (0, 0, 0x1, 0x1) => Instruction::MegaOn,
+            (0, 0, 0xB, _) => Instruction::Scu(n),
+            (0, 0x1, _, _) => Instruction::LdHi(kk),
+            (0, 0x2, _, _) => Instruction::LdPal(kk),
+            (0, 0x3, _, _) => Instruction::SprW(kk),
+            (0, 0x4, _, _) => Instruction::SprH(kk),
+            (0, 0x5, _, _) => Instruction::Alpha(kk),
+            (0, 0x6, 0, _) => Instruction::DigiSnd(n),
+            (0, 0x7, 0, 0) => Instruction::StopSnd,
+            (0, 0x8, 0, _) => Instruction::BMode(n),
+            (0, 0x9, _, _) => Instruction::CCol(kk),
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    fn decode(&self, opcode: u16) -> Instruction {
+        if self.variant == Variant::MegaChip {
+            if let Some(instruction) = self.decode_megachip(opcode) {
+                return instruction;
+            }
+        }
+
+        // Hi-res ROMs clear the screen with a machine code call into the patch
+        if self.variant == Variant::Chip8HiRes && opcode == 0x0230 {
+            return Instruction::Cls;
+        }
+
+        if self.variant == Variant::Chip8X {
+            if let Some(instruction) = self.decode_chip8x(opcode) {
+                return instruction;
+            }
+        }
+
         let nibbles = self.get_nibbles(opcode);
         let (x, y, nnn, kk, n) = self.get_variables(opcode);
 
         match nibbles {
-            (0, 0, 0xC, _) => self.scd(n),
-            (0, 0, 0xD, _) => self.scu(n),
-            (0, 0, 0xE, 0xE) => self.ret()?,
-            (0, 0, 0xE, _) => self.cls(),
-            (0, 0, 0xF, 0xB) => self.scr(),
-            (0, 0, 0xF, 0xC) => self.scl(),
-            (0, 0, 0xF, 0xD) => self.exit(),
-            (0, 0, 0xF, 0xE) => self.low(),
-            (0, 0, 0xF, 0xF) => self.high(),
-            (0x1, _, _, _) => self.jp_addr(nnn),
-            (0x2, _, _, _) => self.call_addr(nnn)?,
-            (0x3, _, _, _) => self.se_vx_byte(x, kk),
-            (0x4, _, _, _) => self.sne_vx_byte(x, kk),
-            (0x5, _, _, 2) => unimplemented!("Save VX..VY to memory starting at I"),
-            (0x5, _, _, 3) => unimplemented!("Load VX..VY from memory starting at I"),
-            (0x5, _, _, _) => self.se_vx_vy(x, y),
-            (0x6, _, _, _) => self.ld_vx_byte(x, kk),
-            (0x7, _, _, _) => self.add_vx_byte(x, kk),
-            (0x8, _, _, 0x0) => self.ld_vx_vy(x, y),
-            (0x8, _, _, 0x1) => self.or_vx_vy(x, y),
-            (0x8, _, _, 0x2) => self.and_vx_vy(x, y),
-            (0x8, _, _, 0x3) => self.xor_vx_vy(x, y),
-            (0x8, _, _, 0x4) => self.add_vx_vy(x, y),
-            (0x8, _, _, 0x5) => self.sub_vx_vy(x, y),
-            (0x8, _, _, 0x6) => self.shr_vx_vy(x),
-            (0x8, _, _, 0x7) => self.subn_vx_vy(x, y),
-            (0x8, _, _, 0xE) => self.shl_vx_vy(x),
-            (0x9, _, _, _) => self.sne_vx_vy(x, y),
-            (0xA, _, _, _) => self.ld_i_addr(nnn),
-            (0xB, _, _, _) => self.jp_v0_addr(nnn),
-            (0xC, _, _, _) => self.rnd_vx_byte(x, kk),
-            (0xD, _, _, _) => self.drw_vx_vy_nibble(x, y, n),
-            (0xE, _, 0x9, 0xE) => self.skp_vx(x),
-            (0xE, _, 0xA, 0x1) => self.sknp_vx(x),
-            (0xF, _, 0x0, 0x1) => unimplemented!("Select drawing planes by bitmask"),
-            (0xF, _, 0x0, 0x7) => self.ld_vx_dt(x),
-            (0xF, _, 0x0, 0xA) => self.ld_vx_k(x),
-            (0xF, _, 0x1, 0x5) => self.ld_dt_vx(x),
-            (0xF, _, 0x1, 0x8) => self.ld_st_vx(x),
-            (0xF, _, 0x1, 0xE) => self.add_i_vx(x),
-            (0xF, _, 0x2, 0x9) => self.ld_f_vx(x),
-            (0xF, _, 0x3, 0x0) => self.ld_hf_vx(x),
-            (0xF, _, 0x3, 0x3) => self.ld_b_vx(x),
-            (0xF, _, 0x5, 0x5) => self.ld_i_vx(x),
-            (0xF, _, 0x7, 0x5) => self.ld_r_vx(),
-            (0xF, _, 0x8, 0x5) => self.ld_vx_r(),
-            (0xF, _, 0x6, 0x5) => self.ld_vx_i(x),
-            (0xF, 0x0, 0x0, 0x2) => unimplemented!("Store 16 bytes in audio pattern buffer, starting at I, to be played by the sound buzzer"),
-            (0xF, 0x0, 0x0, 0x0) => unimplemented!("Load I with 16-bit address NNNN"),
-            (0xF, 0x0, 0x3, 0xA) => unimplemented!("Set the pitch register to the value in VX"),
-            (_, _, _, _) => {
+            (0, 0, 0xC, _) => Instruction::Scd(n),
+            (0, 0, 0xD, _) => Instruction::Scu(n),
+            (0, 0, 0xE, 0xE) => Instruction::Ret,
+            (0, 0, 0xE, _) => Instruction::Cls,
+            (0, 0, 0xF, 0xB) => Instruction::Scr,
+            (0, 0, 0xF, 0xC) => Instruction::Scl,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::Low,
+            (0, 0, 0xF, 0xF) => Instruction::High,
+            (0x1, _, _, _) => Instruction::Jp(nnn),
+            (0x2, _, _, _) => Instruction::Call(nnn),
+            (0x3, _, _, _) => Instruction::SeVxByte(x, kk),
+            (0x4, _, _, _) => Instruction::SneVxByte(x, kk),
+            (0x5, _, _, 2) => Instruction::SaveVxVy,
+            (0x5, _, _, 3) => Instruction::LoadVxVy,
+            (0x5, _, _, _) => Instruction::SeVxVy(x, y),
+            (0x6, _, _, _) => Instruction::LdVxByte(x, kk),
+            (0x7, _, _, _) => Instruction::AddVxByte(x, kk),
+            (0x8, _, _, 0x0) => Instruction::LdVxVy(x, y),
+            (0x8, _, _, 0x1) => Instruction::OrVxVy(x, y),
+            (0x8, _, _, 0x2) => Instruction::AndVxVy(x, y),
+            (0x8, _, _, 0x3) => Instruction::XorVxVy(x, y),
+            (0x8, _, _, 0x4) => Instruction::AddVxVy(x, y),
+            (0x8, _, _, 0x5) => Instruction::SubVxVy(x, y),
+            (0x8, _, _, 0x6) => Instruction::ShrVx(x),
+            (0x8, _, _, 0x7) => Instruction::SubnVxVy(x, y),
+            (0x8, _, _, 0xE) => Instruction::ShlVx(x),
+            (0x9, _, _, _) => Instruction::SneVxVy(x, y),
+            (0xA, _, _, _) => Instruction::LdIAddr(nnn),
+            (0xB, _, _, _) => Instruction::JpV0Addr(nnn),
+            (0xC, _, _, _) => Instruction::RndVxByte(x, kk),
+            (0xD, _, _, _) => Instruction::Drw(x, y, n),
+            (0xE, _, 0x9, 0xE) => Instruction::SkpVx(x),
+            (0xE, _, 0xA, 0x1) => Instruction::SknpVx(x),
+            (0xF, _, 0x0, 0x1) => Instruction::Plane,
+            (0xF, _, 0x0, 0x7) => Instruction::LdVxDt(x),
+            (0xF, _, 0x0, 0xA) => Instruction::LdVxK(x),
+            (0xF, _, 0x1, 0x5) => Instruction::LdDtVx(x),
+            (0xF, _, 0x1, 0x8) => Instruction::LdStVx(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddIVx(x),
+            (0xF, _, 0x2, 0x9) => Instruction::LdFVx(x),
+            (0xF, _, 0x3, 0x0) => Instruction::LdHfVx(x),
+            (0xF, _, 0x3, 0x3) => Instruction::LdBVx(x),
+            (0xF, _, 0x5, 0x5) => Instruction::LdIVx(x),
+            (0xF, _, 0x7, 0x5) => Instruction::LdRVx,
+            (0xF, _, 0x8, 0x5) => Instruction::LdVxR,
+            (0xF, _, 0x6, 0x5) => Instruction::LdVxI(x),
+            (0xF, 0x0, 0x0, 0x2) => Instruction::Audio,
+            (0xF, 0x0, 0x0, 0x0) => Instruction::LdILong,
+            (0xF, 0x0, 0x3, 0xA) => Instruction::Pitch,
+            (_, _, _, _) => Instruction::Unknown(opcode),
+        }
+    }
+
+    /// Decodes the instruction at PC, or takes it from the cache
+    fn fetch(&mut self) -> Result<Instruction, Error> {
+        self.check_access(self.pc as usize, 2)?;
+        self.fetches_since_random += 1;
+
+        // PC can go past the end of smaller memory maps, and wraps like reads do
+        let pc = self.wrap(self.pc as usize);
+
+        if let Some(instruction) = self.instruction_cache.as_ref().and_then(|cache| cache[pc]) {
+            return Ok(instruction);
+        }
+
+        let instruction = self.decode(self.get_opcode());
+
+        if let Some(ref mut cache) = self.instruction_cache {
+            cache[pc] = Some(instruction);
+        }
+
+        Ok(instruction)
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Error> {
+        match instruction {
+            Instruction::Scd(n) => self.scd(n),
+            Instruction::Scu(n) => self.scu(n),
+            Instruction::Ret => self.ret()?,
+            Instruction::Cls => self.cls(),
+            Instruction::Scr => self.scr(),
+            Instruction::Scl => self.scl(),
+            Instruction::Exit => self.exit(),
+            Instruction::Low => self.low(),
+            Instruction::High => self.high(),
+            Instruction::Jp(nnn) => self.jp_addr(nnn),
+            Instruction::Call(nnn) => self.call_addr(nnn)?,
+            Instruction::SeVxByte(x, kk) => self.se_vx_byte(x, kk),
+            Instruction::SneVxByte(x, kk) => self.sne_vx_byte(x, kk),
+            Instruction::SaveVxVy => unimplemented!("Save VX..VY to memory starting at I"),
+            Instruction::LoadVxVy => unimplemented!("Load VX..VY from memory starting at I"),
+            Instruction::SeVxVy(x, y) => self.se_vx_vy(x, y),
+            Instruction::LdVxByte(x, kk) => self.ld_vx_byte(x, kk),
+            Instruction::AddVxByte(x, kk) => self.add_vx_byte(x, kk),
+            Instruction::LdVxVy(x, y) => self.ld_vx_vy(x, y),
+            Instruction::OrVxVy(x, y) => self.or_vx_vy(x, y),
+            Instruction::AndVxVy(x, y) => self.and_vx_vy(x, y),
+            Instruction::XorVxVy(x, y) => self.xor_vx_vy(x, y),
+            Instruction::AddVxVy(x, y) => self.add_vx_vy(x, y),
+            Instruction::SubVxVy(x, y) => self.sub_vx_vy(x, y),
+            Instruction::ShrVx(x) => self.shr_vx_vy(x),
+            Instruction::SubnVxVy(x, y) => self.subn_vx_vy(x, y),
+            Instruction::ShlVx(x) => self.shl_vx_vy(x),
+            Instruction::SneVxVy(x, y) => self.sne_vx_vy(x, y),
+            Instruction::LdIAddr(nnn) => self.ld_i_addr(nnn),
+            Instruction::JpV0Addr(nnn) => self.jp_v0_addr(nnn),
+            Instruction::RndVxByte(x, kk) => self.rnd_vx_byte(x, kk),
+            Instruction::Drw(x, y, n) => self.drw_vx_vy_nibble(x, y, n)?,
+            Instruction::SkpVx(x) => self.skp_vx(x),
+            Instruction::SknpVx(x) => self.sknp_vx(x),
+            Instruction::Plane => unimplemented!("Select drawing planes by bitmask"),
+            Instruction::LdVxDt(x) => self.ld_vx_dt(x),
+            Instruction::LdVxK(x) => self.ld_vx_k(x),
+            Instruction::LdDtVx(x) => self.ld_dt_vx(x),
+            Instruction::LdStVx(x) => self.ld_st_vx(x),
+            Instruction::AddIVx(x) => self.add_i_vx(x),
+            Instruction::LdFVx(x) => self.ld_f_vx(x),
+            Instruction::LdHfVx(x) => self.ld_hf_vx(x),
+            Instruction::LdBVx(x) => self.ld_b_vx(x)?,
+            Instruction::LdIVx(x) => self.ld_i_vx(x)?,
+            Instruction::LdRVx => self.ld_r_vx(),
+            Instruction::LdVxR => self.ld_vx_r(),
+            Instruction::LdVxI(x) => self.ld_vx_i(x)?,
+            Instruction::Audio => unimplemented!("Store 16 bytes in audio pattern buffer, starting at I, to be played by the sound buzzer"),
+            Instruction::LdILong => unimplemented!("Load I with 16-bit address NNNN"),
+            Instruction::Pitch => unimplemented!("Set the pitch register to the value in VX"),
+            Instruction::Background => self.background(),
+            Instruction::ColorZones(x, y) => self.color_zones(x, y),
+            Instruction::ColorRows(x, y, n) => self.color_rows(x, y, n),
+            Instruction::AddColors(x, y) => self.add_colors(x, y),
+            Instruction::SkpVx2(x) => self.skp_vx_2(x),
+            Instruction::SknpVx2(x) => self.sknp_vx_2(x),
+            Instruction::OutVx(x) => self.out_vx(x),
+            Instruction::InVx(x) => self.in_vx(x),
+            Instruction::MegaOff => self.mega_off(),
+            Instruction::MegaOn => self.mega_on(),
+            Instruction::LdHi(nn) => self.ld_hi(nn),
+            Instruction::LdPal(nn) => self.ld_pal(nn)?,
+            Instruction::SprW(nn) => self.spr_w(nn),
+            Instruction::SprH(nn) => self.spr_h(nn),
+            Instruction::Alpha(nn) => self.alpha(nn),
+            Instruction::DigiSnd(n) => self.digi_snd(n)?,
+            Instruction::StopSnd => self.stop_snd(),
+            Instruction::BMode(n) => self.b_mode(n)?,
+            Instruction::CCol(nn) => self.c_col(nn),
+            Instruction::Unknown(opcode) => {
                 return Err(anyhow::anyhow!(
                     "Unknown opcode: {opcode:#04X} at {0:#04X}",
-                    self.pc - ENTRY_POINT as u16,
+                    usize::from(self.pc).wrapping_sub(self.memory_map.entry_point),
                 ))
             }
         }
 
         Ok(())
     }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self.trap.is_some() {
+            return Ok(());
+        }
+
+        if let Some(ref tracer) = self.tracer {
+            tracer.borrow_mut().trace(&self.trace_entry());
+        }
+
+        let result = self.fetch().and_then(|instruction| self.execute(instruction));
+
+        // A trap isn't a failure, the machine just stays on the instruction
+        if self.trap.is_some() {
+            return Ok(());
+        }
+
+        result
+    }
 }
 
 impl Default for Chip8 {
@@ -688,3 +2094,7 @@ impl Default for Chip8 {
         Self::new()
     }
 }
+
+#[cfg(test)]
+#[path = "tests.rs"]
+mod tests;
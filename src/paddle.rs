@@ -0,0 +1,81 @@
+use super::*;
+
+/// Turns crank rotation into pulses on two keys, for paddle games
+///
+/// Every degree past the dead zone fills a budget scaled by the sensitivity, and each whole unit
+/// of budget holds the key down for one frame, so faster cranking means more frequent pulses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paddle {
+    /// Key pulsed when cranking backward
+    pub backward: u8,
+    /// Key pulsed when cranking forward
+    pub forward: u8,
+    /// Degrees per frame that are ignored
+    pub dead_zone: f32,
+    /// Pulses per degree past the dead zone
+    pub sensitivity: f32,
+    budget: f32,
+}
+
+impl Paddle {
+    /// Budget can't grow past this, so a fast spin doesn't keep moving long after it stops
+    const MAX_BUDGET: f32 = 2.0;
+
+    #[must_use]
+    pub const fn new(backward: u8, forward: u8) -> Self {
+        Self {
+            backward,
+            forward,
+            dead_zone: 0.5,
+            sensitivity: 0.2,
+            budget: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_dead_zone(mut self, dead_zone: f32) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    /// Takes the crank change of this frame in degrees and returns the key to hold, if any
+    pub fn update(&mut self, change: f32) -> Option<u8> {
+        let speed = change.abs() - self.dead_zone;
+
+        if speed <= 0.0 {
+            self.budget = 0.0;
+            return None;
+        }
+
+        // Turning around drops whatever was left from the other direction
+        if self.budget.is_sign_negative() != change.is_sign_negative() {
+            self.budget = 0.0;
+        }
+
+        self.budget += speed.copysign(change) * self.sensitivity;
+        self.budget = self.budget.clamp(-Self::MAX_BUDGET, Self::MAX_BUDGET);
+
+        if self.budget >= 1.0 {
+            self.budget -= 1.0;
+            Some(self.forward)
+        } else if self.budget <= -1.0 {
+            self.budget += 1.0;
+            Some(self.backward)
+        } else {
+            None
+        }
+    }
+
+    /// Presses the pulsed key on top of the keys already pressed
+    pub fn apply(&mut self, change: f32, keys: &mut [bool; KEYS]) {
+        if let Some(key) = self.update(change) {
+            keys[(key & 0xF) as usize] = true;
+        }
+    }
+}
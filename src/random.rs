@@ -0,0 +1,136 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use rand::rngs::SmallRng;
+use rand::RngCore;
+use rand::SeedableRng;
+
+/// Seed used until `Chip8::set_random_seed` picks another one
+pub const DEFAULT_SEED: u64 = 0x5EED_C8C8;
+
+/// Where CXNN gets its random bytes from
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// Called before `next_byte` with how many instructions were fetched since the last call
+    ///
+    /// Instructions skipped in idle loops count too, they ran on the original machine.
+    fn fetched(&mut self, _instructions: u64) {}
+
+    /// Copy of the source in its current state, so save states replay the same numbers
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// `SmallRng` from a fixed seed, the same seed gives the same game
+#[derive(Debug, Clone)]
+pub struct SeededRandom(SmallRng);
+
+impl SeededRandom {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self(SmallRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for SeededRandom {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.0.next_u32().to_le_bytes()[0]
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// The CXNN routine of the COSMAC VIP interpreter
+///
+/// CXNN steps the low byte of R9, adds the interpreter byte it points to in page 1 to the high
+/// byte, and masks the high byte with NN. R9 also goes up by one on every instruction fetch, which
+/// `Chip8` reports through `RandomSource::fetched`.
+///
+/// The interpreter is RCA's and isn't bundled, so the bytes come from a dump of it. R9 holds
+/// whatever the VIP left in it before the interpreter started, so it's a parameter.
+#[derive(Debug, Clone)]
+pub struct VipStyleRandom {
+    page: [u8; 256],
+    r9: u16,
+}
+
+impl VipStyleRandom {
+    #[must_use]
+    pub const fn new(page: [u8; 256], r9: u16) -> Self {
+        Self { page, r9 }
+    }
+
+    /// Takes page 1 of the 512 bytes of the interpreter, loaded from 0x000 on the VIP
+    ///
+    /// Returns `None` if `interpreter` is too short to be one.
+    #[must_use]
+    pub fn from_interpreter(interpreter: &[u8], r9: u16) -> Option<Self> {
+        let page = interpreter.get(0x100..0x200)?.try_into().ok()?;
+        Some(Self::new(page, r9))
+    }
+}
+
+impl RandomSource for VipStyleRandom {
+    fn fetched(&mut self, instructions: u64) {
+        // INC R9 carries into the high byte, and 65536 of them go all the way around
+        let [low, high, ..] = instructions.to_le_bytes();
+        self.r9 = self.r9.wrapping_add(u16::from_le_bytes([low, high]));
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        let [high, low] = self.r9.to_be_bytes();
+        let low = low.wrapping_add(1);
+        let high = high.wrapping_add(self.page[low as usize]);
+
+        self.r9 = u16::from_be_bytes([high, low]);
+        high
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// Plays back a fixed list of bytes over and over, for tests
+#[derive(Debug, Clone)]
+pub struct ScriptedRandom {
+    bytes: Vec<u8>,
+    index: usize,
+}
+
+impl ScriptedRandom {
+    /// # Panics
+    ///
+    /// Panics if `bytes` is empty
+    #[must_use]
+    pub fn new(bytes: Vec<u8>) -> Self {
+        assert!(!bytes.is_empty(), "ScriptedRandom needs at least one byte");
+
+        Self { bytes, index: 0 }
+    }
+}
+
+impl RandomSource for ScriptedRandom {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.index];
+        self.index = (self.index + 1) % self.bytes.len();
+        byte
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
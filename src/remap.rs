@@ -0,0 +1,78 @@
+use alloc::format;
+
+use super::*;
+
+/// Lists every Playdate input with the keys bound to it
+///
+/// Up and down pick the input, left and right pick the key, A toggles the binding and B closes.
+pub struct RemapScreen {
+    input: usize,
+    key: u8,
+}
+
+impl RemapScreen {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { input: 0, key: 0 }
+    }
+
+    /// Returns false once the user is done
+    pub fn update(&mut self, key_map: &mut KeyMap) -> bool {
+        let pushed = Buttons::Cached().pushed();
+
+        if pushed.up() {
+            self.input = (self.input + INPUTS - 1) % INPUTS;
+        }
+        if pushed.down() {
+            self.input = (self.input + 1) % INPUTS;
+        }
+        if pushed.left() {
+            self.key = (self.key + KEYS as u8 - 1) % KEYS as u8;
+        }
+        if pushed.right() {
+            self.key = (self.key + 1) % KEYS as u8;
+        }
+        if pushed.a() {
+            key_map.toggle(Input::ALL[self.input], self.key);
+        }
+        if pushed.b() {
+            return false;
+        }
+
+        self.draw(key_map);
+
+        true
+    }
+
+    fn draw(&self, key_map: &KeyMap) {
+        let graphics = Graphics::Cached();
+        graphics.clear_raw(1);
+
+        for (row, input) in Input::ALL.iter().enumerate() {
+            let cursor = if row == self.input { ">" } else { " " };
+            let mut keys = format!("{cursor} {}:", input.name());
+
+            for key in 0..KEYS as u8 {
+                if key_map.is_bound(*input, key) {
+                    keys.push_str(&format!(" {key:X}"));
+                }
+            }
+
+            graphics.draw_text(keys, 8, 8 + row as i32 * 20).unwrap();
+        }
+
+        graphics
+            .draw_text(
+                format!("Key {:X}    A: toggle    B: done", self.key),
+                8,
+                LCD_ROWS as i32 - 24,
+            )
+            .unwrap();
+    }
+}
+
+impl Default for RemapScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
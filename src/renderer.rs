@@ -0,0 +1,266 @@
+use crate::chip8x::{ColorMap, CHIP8X_COLORS};
+use crate::framebuffer::{Framebuffer, FRAMEBUFFER_WIDTH};
+use crate::megachip::{luminance, MegaScreen};
+
+// Same as LCD_COLUMNS, LCD_ROWS and LCD_ROWSIZE, kept here so rendering runs off-device
+pub const LCD_WIDTH: usize = 400;
+pub const LCD_HEIGHT: usize = 240;
+pub const LCD_ROW_BYTES: usize = 52;
+
+// Ordered dither thresholds, scaled to 0-255 by `dithered`
+const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Whether a pixel of this brightness is white at (x, y) of the dither pattern
+#[inline]
+const fn dithered(luminance: u32, x: usize, y: usize) -> bool {
+    luminance > BAYER[y % 4][x % 4] * 16 + 8
+}
+
+/// Scales the framebuffer onto the 1-bit LCD
+///
+/// Each emulated row is expanded once into LCD row bytes and then copied `scale` times, and only
+/// rows the framebuffer marked as dirty are touched.
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    scale: usize,
+    area_width: usize,
+    padding_x: usize,
+    padding_y: usize,
+    // Bits of an LCD row covered by the game
+    mask: [u8; LCD_ROW_BYTES],
+    row: [u8; LCD_ROW_BYTES],
+    // Layout changed, everything has to be drawn again
+    stale: bool,
+}
+
+impl Renderer {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            scale: 0,
+            area_width: 0,
+            padding_x: 0,
+            padding_y: 0,
+            mask: [0; LCD_ROW_BYTES],
+            row: [0; LCD_ROW_BYTES],
+            stale: true,
+        }
+    }
+
+    /// Centers a `width` by `height` screen scaled by `scale` in the leftmost `area_width` pixels
+    pub fn set_layout(&mut self, width: usize, height: usize, scale: usize, area_width: usize) {
+        if (width, height, scale, area_width)
+            == (self.width, self.height, self.scale, self.area_width)
+        {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.scale = scale;
+        self.area_width = area_width;
+        self.padding_x = (area_width - width * scale) / 2;
+        self.padding_y = (LCD_HEIGHT - height * scale) / 2;
+
+        self.mask = [0; LCD_ROW_BYTES];
+        for x in self.padding_x..self.padding_x + width * scale {
+            self.mask[x / 8] |= 0x80 >> (x % 8);
+        }
+
+        self.stale = true;
+    }
+
+    /// LCD row where the emulated row starts
+    #[inline]
+    #[must_use]
+    pub const fn lcd_row(&self, y: usize) -> usize {
+        self.padding_y + y * self.scale
+    }
+
+    /// Expands an emulated row into LCD row bytes, lit pixels are black
+    ///
+    /// Only the bits covered by the game are meaningful.
+    pub fn expand_row(&mut self, pixels: u128) -> &[u8; LCD_ROW_BYTES] {
+        let mut byte = self.padding_x / 8;
+        // Bits before the game in the first byte are masked out anyway
+        let mut bits = self.padding_x % 8;
+        let mut acc: u32 = 0;
+        let run = (1 << self.scale) - 1;
+
+        for x in 0..self.width {
+            let on = pixels & (1 << (FRAMEBUFFER_WIDTH - 1 - x)) != 0;
+
+            acc = (acc << self.scale) | if on { 0 } else { run };
+            bits += self.scale;
+
+            while bits >= 8 {
+                bits -= 8;
+                self.row[byte] = (acc >> bits).to_le_bytes()[0];
+                byte += 1;
+            }
+        }
+
+        if bits > 0 {
+            self.row[byte] = (acc << (8 - bits)).to_le_bytes()[0];
+        }
+
+        &self.row
+    }
+
+    /// Draws the dirty rows into the LCD frame and reports each updated range of LCD rows
+    ///
+    /// `mark` gets the first and last LCD row of every contiguous run of redrawn rows.
+    pub fn render(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        frame: &mut [u8],
+        mark: impl FnMut(usize, usize),
+    ) {
+        self.render_dirty(framebuffer, frame, mark, |renderer, frame, y, pixels| {
+            renderer.expand_row(pixels);
+
+            for lcd_y in renderer.lcd_row(y)..renderer.lcd_row(y + 1) {
+                renderer.copy_row(frame, lcd_y);
+            }
+        });
+    }
+
+    /// Like `render`, with lit and unlit pixels dithered to their CHIP-8X colors
+    pub fn render_colors(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        colors: &ColorMap,
+        frame: &mut [u8],
+        mark: impl FnMut(usize, usize),
+    ) {
+        self.render_dirty(framebuffer, frame, mark, |renderer, frame, y, pixels| {
+            for lcd_y in renderer.lcd_row(y)..renderer.lcd_row(y + 1) {
+                renderer.dither_colors(pixels, colors, y, lcd_y);
+                renderer.copy_row(frame, lcd_y);
+            }
+        });
+    }
+
+    /// Dithers an emulated row into the bytes of LCD row `lcd_y`, pixel by pixel
+    pub fn dither_colors(
+        &mut self,
+        pixels: u128,
+        colors: &ColorMap,
+        y: usize,
+        lcd_y: usize,
+    ) -> &[u8; LCD_ROW_BYTES] {
+        for x in 0..self.width {
+            let color = if pixels & (1 << (FRAMEBUFFER_WIDTH - 1 - x)) != 0 {
+                colors.foreground(x, y)
+            } else {
+                colors.background()
+            };
+            let luminance = luminance(CHIP8X_COLORS[color as usize], 0xFF);
+
+            let start = self.padding_x + x * self.scale;
+            for lcd_x in start..start + self.scale {
+                let bit = 0x80 >> (lcd_x % 8);
+
+                if dithered(luminance, lcd_x, lcd_y) {
+                    self.row[lcd_x / 8] |= bit;
+                } else {
+                    self.row[lcd_x / 8] &= !bit;
+                }
+            }
+        }
+
+        &self.row
+    }
+
+    /// Redraws the dirty rows with `draw` and reports the runs of LCD rows it covered
+    fn render_dirty(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        frame: &mut [u8],
+        mut mark: impl FnMut(usize, usize),
+        mut draw: impl FnMut(&mut Self, &mut [u8], usize, u128),
+    ) {
+        let mut dirty = framebuffer.take_dirty_rows();
+        if self.stale {
+            dirty = u64::MAX;
+            self.stale = false;
+        }
+
+        let mut run_start = None;
+
+        for y in 0..self.height {
+            if dirty & (1 << y) == 0 {
+                if let Some(start) = run_start.take() {
+                    mark(self.lcd_row(start), self.lcd_row(y) - 1);
+                }
+                continue;
+            }
+
+            run_start.get_or_insert(y);
+
+            draw(self, frame, y, framebuffer.row(y));
+        }
+
+        if let Some(start) = run_start {
+            mark(self.lcd_row(start), self.lcd_row(self.height) - 1);
+        }
+    }
+
+    /// Copies the bits of the row bytes covered by the game into LCD row `lcd_y`
+    fn copy_row(&self, frame: &mut [u8], lcd_y: usize) {
+        let line = &mut frame[lcd_y * LCD_ROW_BYTES..(lcd_y + 1) * LCD_ROW_BYTES];
+
+        for ((out, row), mask) in line.iter_mut().zip(&self.row).zip(&self.mask) {
+            *out = (*out & !mask) | (row & mask);
+        }
+    }
+
+    /// Dithers a row of the Mega-Chip screen into LCD row bytes, bright pixels are white
+    ///
+    /// Expects a `MEGA_WIDTH` by `MEGA_HEIGHT` layout at scale 1.
+    pub fn dither_row(&mut self, screen: &MegaScreen, y: usize) -> &[u8; LCD_ROW_BYTES] {
+        for x in 0..self.width {
+            let lcd_x = self.padding_x + x;
+            let bit = 0x80 >> (lcd_x % 8);
+
+            if dithered(luminance(screen.pixel(x, y), screen.alpha), x, y) {
+                self.row[lcd_x / 8] |= bit;
+            } else {
+                self.row[lcd_x / 8] &= !bit;
+            }
+        }
+
+        &self.row
+    }
+
+    /// Draws the Mega-Chip screen into the LCD frame when it shows a new frame
+    ///
+    /// `mark` gets the first and last LCD row drawn, like in `render`.
+    pub fn render_mega(
+        &mut self,
+        screen: &mut MegaScreen,
+        frame: &mut [u8],
+        mut mark: impl FnMut(usize, usize),
+    ) {
+        if !screen.take_presented() && !self.stale {
+            return;
+        }
+        self.stale = false;
+
+        for y in 0..self.height {
+            self.dither_row(screen, y);
+            self.copy_row(frame, self.lcd_row(y));
+        }
+
+        mark(self.lcd_row(0), self.lcd_row(self.height) - 1);
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
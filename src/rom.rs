@@ -0,0 +1,60 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::Error;
+
+use super::*;
+
+/// Folder inside the game's data folder where players copy their own ROMs
+pub const ROM_FOLDER: &str = "roms";
+
+/// Where the bytes of a ROM come from
+pub enum RomSource {
+    /// Compiled into the binary with `include_bytes!`
+    Embedded(&'static [u8]),
+    /// Read at runtime from the game's data folder
+    DataFolder(String),
+    /// Owned bytes, mostly useful for tests
+    Memory(Vec<u8>),
+}
+
+impl RomSource {
+    /// A file in `ROM_FOLDER`
+    #[must_use]
+    pub fn data_folder(name: &str) -> Self {
+        Self::DataFolder(format!("{ROM_FOLDER}/{name}"))
+    }
+
+    /// Reads the whole ROM
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read from the data folder
+    pub fn read(&self) -> Result<Cow<'_, [u8]>, Error> {
+        match self {
+            Self::Embedded(bytes) => Ok(Cow::Borrowed(bytes)),
+            Self::DataFolder(path) => read_data(path).map(Cow::Owned),
+            Self::Memory(bytes) => Ok(Cow::Borrowed(bytes)),
+        }
+    }
+
+    /// Reads the ROM and loads it into the interpreter
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM can't be read or doesn't fit in memory
+    pub fn load_into(&self, cpu: &mut Chip8, tick_rate: Option<u16>) -> Result<(), Error> {
+        let rom = self.read()?;
+        cpu.load_rom(&rom, tick_rate)
+    }
+}
+
+/// Files in `ROM_FOLDER` sorted by name, none if the folder doesn't exist
+#[must_use]
+pub fn data_folder_roms() -> Vec<String> {
+    let mut names = list_data(ROM_FOLDER).unwrap_or_default();
+    names.retain(|name| !name.ends_with('/'));
+    names.sort();
+    names
+}
@@ -0,0 +1,94 @@
+use alloc::vec::Vec;
+use core::ptr;
+use pd::sys::api;
+use pd::sys::ffi::{AudioSample, SamplePlayer, SoundFormat};
+
+use super::*;
+
+/// Plays the Mega-Chip samples started by 060N, see `Chip8::sample`
+pub struct SoundPlayer {
+    player: *mut SamplePlayer,
+    sample: *mut AudioSample,
+    /// The samples as signed 16-bit, which the Playdate reads from while they play
+    data: Vec<u8>,
+    playing: Option<Sample>,
+}
+
+impl SoundPlayer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            player: unsafe { api!(sound.sampleplayer.newPlayer)() },
+            sample: ptr::null_mut(),
+            data: Vec::new(),
+            playing: None,
+        }
+    }
+
+    /// Starts the sample the ROM started, or stops when it stopped or started another one
+    pub fn update(&mut self, sample: Option<(Sample, &[u8])>) {
+        if sample.map(|(sample, _)| sample) == self.playing {
+            return;
+        }
+
+        self.stop();
+
+        if let Some((sample, bytes)) = sample {
+            self.play(sample, bytes);
+        }
+    }
+
+    fn play(&mut self, sample: Sample, bytes: &[u8]) {
+        // Even one that can't play, so it isn't tried again every frame
+        self.playing = Some(sample);
+
+        self.data = to_pcm16(bytes);
+
+        let Ok(len) = i32::try_from(self.data.len()) else {
+            return;
+        };
+        if len == 0 || sample.rate == 0 {
+            return;
+        }
+
+        unsafe {
+            self.sample = api!(sound.sample.newSampleFromData)(
+                self.data.as_mut_ptr(),
+                SoundFormat::kSound16bitMono,
+                u32::from(sample.rate),
+                len,
+                0,
+            );
+            api!(sound.sampleplayer.setSample)(self.player, self.sample);
+            // Plays once for 1, and loops until stopped for 0
+            api!(sound.sampleplayer.play)(self.player, i32::from(!sample.looping), 1.0);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        unsafe {
+            api!(sound.sampleplayer.stop)(self.player);
+
+            if !self.sample.is_null() {
+                api!(sound.sample.freeSample)(self.sample);
+            }
+        }
+
+        self.sample = ptr::null_mut();
+        self.data = Vec::new();
+        self.playing = None;
+    }
+}
+
+impl Default for SoundPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SoundPlayer {
+    fn drop(&mut self) {
+        self.stop();
+        unsafe { api!(sound.sampleplayer.freePlayer)(self.player) };
+    }
+}
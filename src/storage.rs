@@ -0,0 +1,49 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::Error;
+use pd::fs::options::FileOptionsExt;
+use pd::fs::{FileOptions, Fs};
+
+use super::*;
+
+/// Reads a whole file, looking in the data folder first and then in the bundle
+///
+/// # Errors
+///
+/// Returns an error if the file doesn't exist or can't be read
+pub fn read_data(path: &str) -> Result<Vec<u8>, Error> {
+    pd::fs::read(path, true).map_err(|e| anyhow::anyhow!("Cannot read {path}: {e:?}"))
+}
+
+/// Names of the files and folders in a folder, folders ending with `/`
+///
+/// # Errors
+///
+/// Returns an error if the folder doesn't exist or can't be listed
+pub fn list_data(path: &str) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+
+    Fs::Cached()
+        .read_dir(path, |name| names.push(name), false)
+        .map_err(|e| anyhow::anyhow!("Cannot list {path}: {e:?}"))?;
+
+    Ok(names)
+}
+
+/// Replaces a file in the data folder
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened or written
+pub fn write_data(path: &str, bytes: &[u8]) -> Result<(), Error> {
+    let fs = Fs::Cached();
+
+    let mut file = fs
+        .open(path, FileOptions::new().write(true))
+        .map_err(|e| anyhow::anyhow!("Cannot open {path}: {e:?}"))?;
+
+    fs.write(&mut file, bytes)
+        .map_err(|e| anyhow::anyhow!("Cannot write {path}: {e:?}"))?;
+
+    Ok(())
+}
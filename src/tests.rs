@@ -1,10 +1,28 @@
 use super::*;
+use crate::analyzer::*;
+use crate::cfg::*;
+use crate::chip8x::*;
+use crate::font::*;
+use crate::framebuffer::*;
+use crate::database::ROMS;
+use crate::keymap::*;
+use crate::keypad::Keypad;
+use crate::megachip::*;
+use crate::paddle::Paddle;
+use crate::random::*;
+use crate::tilt::*;
+use crate::trace::*;
+use crate::renderer::*;
+use crate::rom::RomSource;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 
 macro_rules! test_opcode {
     ($cpu:expr,$opcode:expr,$entry_point:expr) => {{
         $cpu.memory[$entry_point] = (($opcode & 0xFF00) >> 8) as u8;
         $cpu.memory[$entry_point + 1] = ($opcode & 0x00FF) as u8;
-        $cpu.tick();
+        $cpu.tick().unwrap();
     }};
 }
 
@@ -12,7 +30,11 @@ macro_rules! test_opcode {
 fn test_cls() {
     let mut cpu = Chip8::new();
 
-    cpu.gfx_buffer = [true; (SCREEN_WIDTH * 2) * (SCREEN_HEIGHT * 2)];
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            cpu.gfx_buffer.set(x, y, true);
+        }
+    }
 
     // CLS
     test_opcode!(cpu, 0x00E0, ENTRY_POINT);
@@ -20,7 +42,7 @@ fn test_cls() {
     assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
     assert_eq!(
         cpu.gfx_buffer,
-        [false; (SCREEN_WIDTH * 2) * (SCREEN_HEIGHT * 2)]
+        Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT)
     );
 }
 
@@ -62,255 +84,1854 @@ fn test_call_addr() {
 #[test]
 fn test_se_vx_byte() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x12;
 
-    test_opcode!(cpu, 0x3000, ENTRY_POINT);
+    test_opcode!(cpu, 0x3012, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 4) as u16);
 }
 
 #[test]
 fn test_sne_vx_byte() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x12;
 
-    test_opcode!(cpu, 0x4000, ENTRY_POINT);
+    test_opcode!(cpu, 0x4012, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
 }
 
 #[test]
 fn test_se_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[1] = 0x34;
 
-    test_opcode!(cpu, 0x5000, ENTRY_POINT);
+    test_opcode!(cpu, 0x5010, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
 }
 
 #[test]
 fn test_ld_vx_byte() {
     let mut cpu = Chip8::new();
 
-    test_opcode!(cpu, 0x6000, ENTRY_POINT);
+    test_opcode!(cpu, 0x6012, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x12);
 }
 
 #[test]
 fn test_add_vx_byte() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0xF0;
 
-    test_opcode!(cpu, 0x7000, ENTRY_POINT);
+    test_opcode!(cpu, 0x7012, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x02);
+    assert_eq!(cpu.v[0xF], 0);
 }
 
 #[test]
 fn test_ld_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[1] = 0x34;
 
-    test_opcode!(cpu, 0x8000, ENTRY_POINT);
+    test_opcode!(cpu, 0x8010, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x34);
 }
 
 #[test]
 fn test_or_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x0C;
+    cpu.v[1] = 0x30;
 
-    test_opcode!(cpu, 0x8001, ENTRY_POINT);
+    test_opcode!(cpu, 0x8011, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x3C);
 }
 
 #[test]
 fn test_and_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x3C;
+    cpu.v[1] = 0x0F;
 
-    test_opcode!(cpu, 0x8002, ENTRY_POINT);
+    test_opcode!(cpu, 0x8012, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x0C);
 }
 
 #[test]
 fn test_xor_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x3C;
+    cpu.v[1] = 0x0F;
 
-    test_opcode!(cpu, 0x8003, ENTRY_POINT);
+    test_opcode!(cpu, 0x8013, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x33);
 }
 
 #[test]
 fn test_add_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0xF0;
+    cpu.v[1] = 0x20;
 
-    test_opcode!(cpu, 0x8004, ENTRY_POINT);
+    test_opcode!(cpu, 0x8014, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x10);
+    assert_eq!(cpu.v[0xF], 1);
 }
 
 #[test]
 fn test_sub_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x10;
+    cpu.v[1] = 0x20;
 
-    test_opcode!(cpu, 0x8005, ENTRY_POINT);
+    test_opcode!(cpu, 0x8015, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0xF0);
+    assert_eq!(cpu.v[0xF], 0);
 }
 
 #[test]
 fn test_shr_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x05;
 
-    test_opcode!(cpu, 0x8006, ENTRY_POINT);
+    test_opcode!(cpu, 0x8016, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x02);
+    assert_eq!(cpu.v[0xF], 1);
 }
 
 #[test]
 fn test_subn_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x10;
+    cpu.v[1] = 0x20;
 
-    test_opcode!(cpu, 0x8007, ENTRY_POINT);
+    test_opcode!(cpu, 0x8017, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x10);
+    assert_eq!(cpu.v[0xF], 1);
 }
 
 #[test]
 fn test_shl_vx_vy() {
     let mut cpu = Chip8::new();
+    cpu.v[0] = 0x81;
 
-    test_opcode!(cpu, 0x800E, ENTRY_POINT);
+    test_opcode!(cpu, 0x801E, ENTRY_POINT);
 
-    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.v[0], 0x02);
+    assert_eq!(cpu.v[0xF], 1);
+}
+
+#[test]
+fn test_load_rom() {
+    let mut cpu = Chip8::new();
+
+    let rom = RomSource::Memory(vec![0x12, 0x34]);
+    assert!(rom.load_into(&mut cpu, None).is_ok());
+
+    assert_eq!(cpu.get_opcode(), 0x1234);
+}
+
+#[test]
+fn test_load_rom_too_large() {
+    let mut cpu = Chip8::new();
+
+    let rom = RomSource::Memory(vec![0; cpu.max_rom_size() + 1]);
+    assert!(rom.load_into(&mut cpu, None).is_err());
+
+    cpu.set_variant(Variant::XoChip);
+    assert!(rom.load_into(&mut cpu, None).is_ok());
+}
+
+#[test]
+fn test_load_rom_empty() {
+    let mut cpu = Chip8::new();
+
+    let rom = RomSource::Memory(vec![]);
+    assert!(rom.load_into(&mut cpu, None).is_err());
+}
+
+#[test]
+fn test_data_folder_rom() {
+    let RomSource::DataFolder(path) = RomSource::data_folder("pong.ch8") else {
+        panic!("not in the data folder");
+    };
+    assert_eq!(path, "roms/pong.ch8");
+}
+
+#[test]
+fn test_key_map_apply() {
+    let key_map = KeyMap::empty()
+        .with(Input::Up, 0x2)
+        .with(Input::A, 0x2)
+        .with(Input::A, 0xF);
+
+    let mut keys = [true; KEYS];
+    let mut active = [false; INPUTS];
+    active[Input::A as usize] = true;
+
+    key_map.apply(&active, &mut keys);
+
+    assert!(keys[0x2]);
+    assert!(keys[0xF]);
+    assert_eq!(keys.iter().filter(|k| **k).count(), 2);
+}
+
+#[test]
+fn test_key_map_bytes() {
+    let mut key_map = KeyMap::octo().with(Input::CrankForward, 0xC);
+    key_map.toggle(Input::Up, 0x5);
+
+    assert!(!key_map.is_bound(Input::Up, 0x5));
+    assert_eq!(KeyMap::from_bytes(&key_map.to_bytes()), Some(key_map));
+    assert_eq!(KeyMap::from_bytes(&[0; 3]), None);
+}
+
+#[test]
+fn test_paddle_dead_zone() {
+    let mut paddle = Paddle::new(0x4, 0x6).with_dead_zone(2.0);
+
+    assert_eq!(paddle.update(1.5), None);
+    assert_eq!(paddle.update(-2.0), None);
+}
+
+#[test]
+fn test_paddle_pulse_rate() {
+    let mut slow = Paddle::new(0x4, 0x6).with_sensitivity(0.1);
+    let mut fast = Paddle::new(0x4, 0x6).with_sensitivity(0.1);
+
+    let slow_pulses = (0..30).filter_map(|_| slow.update(5.5)).count();
+    let fast_pulses = (0..30).filter_map(|_| fast.update(10.5)).count();
+
+    assert_eq!(slow_pulses, 15);
+    assert_eq!(fast_pulses, 30);
+}
+
+#[test]
+fn test_paddle_direction() {
+    let mut paddle = Paddle::new(0x4, 0x6).with_dead_zone(0.0).with_sensitivity(1.0);
+    let mut keys = [false; KEYS];
+
+    paddle.apply(1.5, &mut keys);
+    assert!(keys[0x6]);
+
+    // Leftover budget from the forward turn is dropped
+    assert_eq!(paddle.update(-0.5), None);
+    assert_eq!(paddle.update(-0.5), Some(0x4));
+}
+
+#[test]
+fn test_tilt_calibration() {
+    let mut tilt = Tilt::new().with_x(0x7, 0x9);
+
+    // First reading is the rest position, however tilted it is
+    tilt.update(0.5, 0.0);
+    assert!(!tilt.is_active(TiltDirection::Right));
+
+    tilt.update(0.8, 0.0);
+    assert!(tilt.is_active(TiltDirection::Right));
+
+    tilt.calibrate();
+    tilt.update(0.0, 0.0);
+    tilt.update(-0.3, 0.0);
+    assert!(tilt.is_active(TiltDirection::Left));
+}
+
+#[test]
+fn test_tilt_hysteresis() {
+    let mut tilt = Tilt::new().with_y(0x6, 0x8).with_threshold(0.3, 0.1);
+    let mut keys = [false; KEYS];
+
+    tilt.apply(0.0, 0.0, &mut keys);
+    tilt.apply(0.0, 0.35, &mut keys);
+    assert!(keys[0x8]);
+
+    // Still pressed between the release point and the threshold
+    tilt.update(0.0, 0.25);
+    assert!(tilt.is_active(TiltDirection::Down));
+
+    tilt.update(0.0, 0.15);
+    assert!(!tilt.is_active(TiltDirection::Down));
+
+    // Not pressed again until the threshold
+    tilt.update(0.0, 0.25);
+    assert!(!tilt.is_active(TiltDirection::Down));
+}
+
+#[test]
+fn test_keypad_cursor() {
+    let mut keypad = Keypad::new(false);
+    assert_eq!(keypad.selected(), 0x1);
+
+    keypad.left();
+    assert_eq!(keypad.selected(), 0xC);
+
+    keypad.up();
+    keypad.right();
+    assert_eq!(keypad.selected(), 0xA);
+}
+
+#[test]
+fn test_keypad_press() {
+    let mut keypad = Keypad::new(true);
+    let mut keys = [false; KEYS];
+    let mut second_keys = [false; KEYS];
+
+    // Closed keypad doesn't press anything
+    keypad.set_pressed(true);
+    keypad.apply(&mut keys, &mut second_keys);
+    assert!(!keys.contains(&true));
+
+    keypad.open();
+    assert!(!keypad.should_run());
+
+    keypad.down();
+    keypad.set_pressed(true);
+    keypad.apply(&mut keys, &mut second_keys);
+    assert!(keys[0x4]);
+    assert!(!second_keys.contains(&true));
+    assert!(keypad.should_run());
+}
+
+#[test]
+fn test_keypad_second() {
+    let mut keypad = Keypad::new(false).with_second_keypad();
+    let mut keys = [false; KEYS];
+    let mut second_keys = [false; KEYS];
+    keypad.open();
+
+    // Left of the first keypad is the right edge of the second
+    keypad.left();
+    assert!(keypad.is_second());
+    assert_eq!(keypad.cursor(), (3, 0));
+    assert_eq!(keypad.selected(), 0xC);
+
+    keypad.set_pressed(true);
+    keypad.apply(&mut keys, &mut second_keys);
+    assert!(second_keys[0xC]);
+    assert!(!keys.contains(&true));
+
+    keypad.right();
+    assert!(!keypad.is_second());
+    assert_eq!(keypad.selected(), 0x1);
+}
+
+#[test]
+fn test_run_for_timers() {
+    let mut cpu = Chip8::new();
+    cpu.load_rom(&[0x12, 0x00], None).unwrap();
+    cpu.dt = 60;
+
+    // 30 fps worth of frames that don't divide a second evenly
+    for _ in 0..30 {
+        cpu.run_for(33_333).unwrap();
+    }
+    assert_eq!(cpu.dt, 1);
+
+    cpu.run_for(10).unwrap();
+    assert_eq!(cpu.dt, 0);
+}
+
+#[test]
+fn test_run_for_speed() {
+    let mut cpu = Chip8::new();
+    // ADD V0, 1 and jump back
+    cpu.load_rom(&[0x70, 0x01, 0x12, 0x00], None).unwrap();
+    cpu.set_speed(120);
+
+    for _ in 0..10 {
+        cpu.run_for(100_000).unwrap();
+    }
+    assert_eq!(cpu.v[0], 60);
+
+    // Long pauses are not caught up on
+    cpu.run_for(10_000_000).unwrap();
+    assert_eq!(cpu.v[0], 75);
+}
+
+#[test]
+fn test_vip_timing() {
+    let mut cpu = Chip8::new();
+    // ADD V0, 1 and jump back, 33 machine cycles per loop
+    cpu.load_rom(&[0x70, 0x01, 0x12, 0x00], None).unwrap();
+    cpu.set_timing(Timing::Vip);
+
+    cpu.update().unwrap();
+    assert_eq!(cpu.v[0], 79);
+
+    // The cycles the last jump overran by come out of the next frame
+    cpu.update().unwrap();
+    assert_eq!(cpu.v[0], 158);
+}
+
+#[test]
+fn test_display_wait() {
+    let mut cpu = Chip8::new();
+    // Draw a row of the 0 glyph and jump back
+    cpu.load_rom(&[0xD0, 0x01, 0x12, 0x00], Some(10)).unwrap();
+
+    cpu.update().unwrap();
+    assert_eq!(cpu.pc, 0x200);
+
+    cpu.set_quirks(Quirks {
+        display_wait: true,
+        ..Quirks::default()
+    });
+
+    // Frame ends right after the draw
+    cpu.update().unwrap();
+    assert_eq!(cpu.pc, 0x202);
+
+    cpu.update().unwrap();
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn test_framebuffer_xor_row() {
+    let mut framebuffer = Framebuffer::new(64, 32);
+
+    assert!(!framebuffer.xor_row(60, 0, 0b1010_0001, 8));
+    assert!(framebuffer.get(60, 0));
+    assert!(framebuffer.get(62, 0));
+
+    // Wraps around the right edge, and nothing lands past the visible width
+    assert!(framebuffer.get(3, 0));
+    assert_eq!(framebuffer.row(0).count_ones(), 3);
+
+    assert!(framebuffer.xor_row(124, 32, 0b1000_0000, 8));
+    assert_eq!(framebuffer.row(0).count_ones(), 2);
+}
+
+#[test]
+fn test_framebuffer_scroll() {
+    let mut framebuffer = Framebuffer::new(128, 64);
+    framebuffer.set(0, 0, true);
+    framebuffer.set(127, 63, true);
+
+    framebuffer.scroll_down(4);
+    assert!(framebuffer.get(0, 4));
+    assert_eq!(framebuffer.rows().iter().map(|row| row.count_ones()).sum::<u32>(), 1);
+
+    framebuffer.scroll_right(4);
+    assert!(framebuffer.get(4, 4));
+
+    framebuffer.scroll_left(8);
+    assert_eq!(framebuffer.row(4), 0);
+}
+
+#[test]
+fn test_framebuffer_dirty_rows() {
+    let mut framebuffer = Framebuffer::new(64, 32);
+    assert_eq!(framebuffer.take_dirty_rows(), 0xFFFF_FFFF);
+    assert_eq!(framebuffer.take_dirty_rows(), 0);
+
+    // Empty sprite rows don't change anything
+    framebuffer.xor_row(0, 5, 0, 8);
+    assert_eq!(framebuffer.dirty_rows(), 0);
+
+    framebuffer.xor_row(0, 33, 0xFF, 8);
+    assert_eq!(framebuffer.take_dirty_rows(), 1 << 1);
+}
+
+#[test]
+fn test_drw_dirty_rows() {
+    let mut cpu = Chip8::new();
+    cpu.set_variant(Variant::XoChip);
+    cpu.gfx_buffer.take_dirty_rows();
+    cpu.v[0] = 0;
+    cpu.v[1] = 31;
+    cpu.i = 0x300;
+    cpu.memory[0x300] = 0xFF;
+    cpu.memory[0x301] = 0xFF;
+
+    // The sprite wraps from the bottom row to the top one
+    test_opcode!(cpu, 0xD012, ENTRY_POINT);
+    assert_eq!(cpu.gfx_buffer.dirty_rows(), (1 << 31) | 1);
+}
+
+#[test]
+fn test_renderer_expand_row() {
+    let mut renderer = Renderer::new();
+    renderer.set_layout(64, 32, 6, LCD_WIDTH);
+
+    // 8 pixels of padding, then 6 black bits for the lit pixel
+    let row = renderer.expand_row(1 << (FRAMEBUFFER_WIDTH - 1));
+    assert_eq!(row[1], 0b0000_0011);
+    assert!(row[2..49].iter().all(|&byte| byte == 0xFF));
+
+    let row = renderer.expand_row(0b11 << (FRAMEBUFFER_WIDTH - 64));
+    assert_eq!(row[47], 0b1111_0000);
+    assert_eq!(row[48], 0);
+}
+
+#[test]
+fn test_renderer_dirty_rows() {
+    let mut renderer = Renderer::new();
+    let mut framebuffer = Framebuffer::new(64, 32);
+    let mut frame = vec![0xAA; LCD_ROW_BYTES * LCD_HEIGHT];
+    let mut marked = vec![];
+
+    renderer.set_layout(64, 32, 6, LCD_WIDTH);
+    renderer.render(&mut framebuffer, &mut frame, |start, end| {
+        marked.push((start, end));
+    });
+    assert_eq!(marked, [(24, 215)]);
+
+    // Padding is left alone
+    assert_eq!(frame[24 * LCD_ROW_BYTES], 0xAA);
+    assert_eq!(frame[24 * LCD_ROW_BYTES + 1], 0xFF);
+    assert_eq!(frame[24 * LCD_ROW_BYTES + 49], 0xAA);
+
+    framebuffer.xor_row(0, 3, 0x80, 8);
+    framebuffer.xor_row(0, 5, 0x80, 8);
+    marked.clear();
+    renderer.render(&mut framebuffer, &mut frame, |start, end| {
+        marked.push((start, end));
+    });
+    assert_eq!(marked, [(42, 47), (54, 59)]);
+
+    for y in 42..48 {
+        assert_eq!(frame[y * LCD_ROW_BYTES + 1], 0b0000_0011);
+    }
+    assert_eq!(frame[48 * LCD_ROW_BYTES + 1], 0xFF);
+}
+
+#[test]
+fn test_instruction_cache_matches_interpreter() {
+    // XO-CHIP opcodes and the FX85 in turnover77 aren't implemented yet
+    let roms = ROMS
+        .iter()
+        .filter(|info| info.variant != Variant::XoChip && info.name != "turnover77");
+
+    for info in roms {
+        let mut cpus = [Chip8::new(), Chip8::new()];
+        cpus[1].set_instruction_cache(true);
+
+        for cpu in &mut cpus {
+            cpu.set_variant(info.variant);
+            cpu.load_rom(info.rom, None).unwrap();
+            cpu.set_speed(info.speed);
+
+            for _ in 0..120 {
+                if cpu.run_for(16_667).is_err() {
+                    break;
+                }
+            }
+        }
+
+        let [plain, cached] = &cpus;
+        assert_eq!(plain.pc, cached.pc, "{}", info.name);
+        assert_eq!(plain.v, cached.v, "{}", info.name);
+        assert_eq!(plain.i, cached.i, "{}", info.name);
+        assert_eq!(plain.memory, cached.memory, "{}", info.name);
+        assert_eq!(plain.gfx_buffer, cached.gfx_buffer, "{}", info.name);
+    }
+}
+
+#[test]
+fn test_instruction_cache_invalidation() {
+    let mut cpu = Chip8::new();
+    cpu.set_instruction_cache(true);
+
+    cpu.memory[0x200] = 0x6A;
+    cpu.memory[0x201] = 0x01;
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0xA], 1);
+
+    // Overwrites the cached instruction with 6A02
+    cpu.memory[0x202] = 0xF1;
+    cpu.memory[0x203] = 0x55;
+    cpu.i = 0x200;
+    cpu.v[0] = 0x6A;
+    cpu.v[1] = 0x02;
+    cpu.tick().unwrap();
+
+    cpu.pc = 0x200;
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0xA], 2);
+}
+
+#[test]
+fn test_instruction_cache_wraps_pc() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8));
+    cpu.set_variant(Variant::Chip8);
+    cpu.set_instruction_cache(true);
+
+    // SE V0, 00 at the last word skips past the end of the 4 KiB
+    cpu.memory[0xFFE..0x1000].copy_from_slice(&[0x30, 0x00]);
+    cpu.memory[0x002..0x004].copy_from_slice(&[0x60, 0x42]);
+    cpu.pc = 0xFFE;
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x1002);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0], 0x42);
+}
+
+#[test]
+fn test_idle_skip_delay_loop() {
+    let program = [
+        0x60, 0x30, // V0 = 0x30
+        0xF0, 0x15, // DT = V0
+        0xF1, 0x07, // V1 = DT
+        0x31, 0x00, // Skip if V1 == 0
+        0x12, 0x04, // Jump back to V1 = DT
+        0x12, 0x0A, // Jump to self
+    ];
+    let mut cpus = [Chip8::new(), Chip8::new()];
+    cpus[1].set_idle_skip(false);
+
+    for cpu in &mut cpus {
+        cpu.load_rom(&program, None).unwrap();
+        for _ in 0..60 {
+            cpu.run_for(16_667).unwrap();
+        }
+    }
+
+    let [skipping, plain] = &cpus;
+    assert_eq!(skipping.pc, 0x20A);
+    assert_eq!(skipping.pc, plain.pc);
+    assert_eq!(skipping.v, plain.v);
+    assert_eq!(skipping.dt, plain.dt);
+    // Nearly all of the 600 instructions were spent waiting
+    assert!(skipping.skipped_cycles() > 400);
+    assert_eq!(plain.skipped_cycles(), 0);
+}
+
+#[test]
+fn test_idle_skip_key_wait() {
+    let mut cpu = Chip8::new();
+    cpu.load_rom(&[0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04], None).unwrap();
+
+    cpu.run_for(100_000).unwrap();
+    assert_eq!(cpu.pc, 0x200);
+    assert_eq!(cpu.skipped_cycles(), 60);
+
+    cpu.keys[0] = true;
+    cpu.run_for(16_667).unwrap();
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_idle_skip_matches_interpreter() {
+    let roms = ROMS
+        .iter()
+        .filter(|info| info.variant != Variant::XoChip && info.name != "turnover77");
+
+    for info in roms {
+        let mut cpus = [Chip8::new(), Chip8::new()];
+        cpus[1].set_idle_skip(false);
+
+        for cpu in &mut cpus {
+            cpu.set_variant(info.variant);
+            cpu.load_rom(info.rom, None).unwrap();
+            cpu.set_speed(info.speed);
+            cpu.set_timing(info.timing);
+
+            for _ in 0..120 {
+                if cpu.run_for(16_667).is_err() {
+                    break;
+                }
+            }
+        }
+
+        let [skipping, plain] = &cpus;
+        assert_eq!(skipping.pc, plain.pc, "{}", info.name);
+        assert_eq!(skipping.v, plain.v, "{}", info.name);
+        assert_eq!(skipping.vip_cycles, plain.vip_cycles, "{}", info.name);
+        assert_eq!(skipping.memory, plain.memory, "{}", info.name);
+        assert_eq!(skipping.gfx_buffer, plain.gfx_buffer, "{}", info.name);
+    }
 }
 
-/*
 #[test]
-fn test_sne_vx_vy() {
+fn test_memory_wrap() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::Chip8);
+    cpu.v[0] = 123;
+    cpu.i = 0xFFF;
+
+    // BCD of 123 lands on 0xFFF, 0x000 and 0x001
+    test_opcode!(cpu, 0xF033, ENTRY_POINT);
+    assert_eq!(cpu.memory[0xFFF], 1);
+    assert_eq!(cpu.memory[0x000], 2);
+    assert_eq!(cpu.memory[0x001], 3);
+    assert_eq!(cpu.memory[0x1000], 0);
+
+    // XO-CHIP only wraps at 64 KiB
+    cpu.set_variant(Variant::XoChip);
+    cpu.i = 0xFFF;
+    test_opcode!(cpu, 0xF033, ENTRY_POINT + 2);
+    assert_eq!(cpu.memory[0x1000], 2);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_memory_error() {
+    let mut cpu = Chip8::new();
+    cpu.set_quirks(Quirks {
+        memory: MemoryPolicy::Error,
+        ..Quirks::default()
+    });
+    cpu.v[0] = 0xAA;
+    cpu.v[1] = 0xBB;
+    cpu.i = 0xFFF;
+    cpu.memory[0x200] = 0xF1;
+    cpu.memory[0x201] = 0x55;
+
+    // Nothing is written, not even the byte that fits
+    assert!(cpu.tick().is_err());
+    assert_eq!(cpu.memory[0xFFF], 0);
+    assert_eq!(cpu.i, 0xFFF);
+    assert_eq!(cpu.pc, 0x200);
+    assert_eq!(cpu.trap(), None);
+
+    cpu.pc = 0xFFF;
+    assert!(cpu.tick().is_err());
+}
+
+#[test]
+fn test_memory_policy_for_variant() {
+    let mut cpu = Chip8::new();
+    cpu.set_variant(Variant::SuperChip);
+    cpu.load_rom(&[0xF0, 0x33], None).unwrap();
+    cpu.i = 0xFFF;
+    let memory = cpu.memory.clone();
+
+    // The HP48 has more memory past 4 KiB, so nothing wraps back to 0x000
+    assert!(cpu.tick().is_err());
+    assert_eq!(cpu.memory, memory);
+
+    assert_eq!(Quirks::for_variant(Variant::Chip8X).memory, MemoryPolicy::Wrap);
+    assert_eq!(Quirks::for_variant(Variant::MegaChip).memory, MemoryPolicy::Error);
+}
+
+#[test]
+fn test_memory_trap() {
+    let mut cpu = Chip8::new();
+    cpu.set_quirks(Quirks {
+        memory: MemoryPolicy::Trap,
+        ..Quirks::default()
+    });
+    cpu.load_rom(&[0xF0, 0x65, 0x12, 0x02], None).unwrap();
+    cpu.i = 0x1000;
+
+    cpu.run_for(100_000).unwrap();
+    assert_eq!(
+        cpu.trap(),
+        Some(MemoryTrap {
+            pc: 0x200,
+            address: 0x1000,
+            len: 1,
+        })
+    );
+    assert_eq!(cpu.pc, 0x200);
+
+    cpu.i = 0x200;
+    cpu.resume();
+    cpu.run_for(16_667).unwrap();
+    assert_eq!(cpu.v[0], 0xF0);
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn test_add_i_vx_overflow() {
+    let mut cpu = Chip8::new();
+    cpu.i = 0xFFFF;
+    cpu.v[0] = 2;
+
+    test_opcode!(cpu, 0xF01E, ENTRY_POINT);
+    assert_eq!(cpu.i, 1);
+}
+
+#[test]
+fn test_stack_depth() {
+    for (variant, depth) in [(Variant::Chip8, 12), (Variant::SuperChip, 16)] {
+        let mut cpu = Chip8::new();
+        cpu.set_variant(variant);
+
+        // Calls itself until the stack is full
+        cpu.memory[0x200] = 0x22;
+        cpu.memory[0x201] = 0x00;
+        for _ in 0..depth {
+            cpu.tick().unwrap();
+        }
+
+        let error = cpu.tick().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<StackError>(),
+            Some(&StackError::Overflow { pc: 0x200, depth })
+        );
+        assert_eq!(cpu.sp as usize, depth);
+    }
+}
+
+#[test]
+fn test_stack_underflow() {
+    let mut cpu = Chip8::new();
+    cpu.memory[0x200] = 0x00;
+    cpu.memory[0x201] = 0xEE;
+
+    let error = cpu.tick().unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<StackError>(),
+        Some(&StackError::Underflow { pc: 0x200 })
+    );
+}
+
+#[test]
+fn test_default_quirks_call() {
+    let mut cpu = Chip8::new();
+    cpu.set_quirks(Quirks::default());
+
+    test_opcode!(cpu, 0x2300, ENTRY_POINT);
+    assert_eq!(cpu.sp, 1);
+    test_opcode!(cpu, 0x00EE, 0x300);
+    assert_eq!(cpu.pc, (ENTRY_POINT + 2) as u16);
+    assert_eq!(cpu.sp, 0);
 }
 
 #[test]
-fn test_ld_i_addr() {
+fn test_stack_in_memory() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::Chip8);
+    cpu.set_quirks(Quirks {
+        stack_in_memory: true,
+        ..cpu.quirks()
+    });
 
-    assert_eq!(cpu.pc, 100);
+    test_opcode!(cpu, 0x2ABC, ENTRY_POINT);
+    test_opcode!(cpu, 0x2DEF, 0x0ABC);
+    assert_eq!(cpu.memory[0xECE..0xED0], [0x02, 0x00]);
+    assert_eq!(cpu.memory[0xECC..0xECE], [0x0A, 0xBC]);
+    assert_eq!(cpu.stack, [0; STACK_SIZE]);
+
+    // ROMs can rewrite where they return to
+    cpu.memory[0xECD] = 0xC0;
+    test_opcode!(cpu, 0x00EE, 0x0DEF);
+    assert_eq!(cpu.pc, 0x0AC2);
 }
 
 #[test]
-fn test_jp_v0_addr() {
+fn test_cold_reset() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::Chip8);
+    cpu.set_speed(1000);
+    cpu.load_rom(&[0x00, 0xFF, 0x60, 0x2A, 0xA3, 0x00], None).unwrap();
+    cpu.keys[3] = true;
+    for _ in 0..3 {
+        cpu.tick().unwrap();
+    }
+    cpu.dt = 10;
+
+    cpu.cold_reset();
+    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.v, [0; REGISTERS]);
+    assert_eq!(cpu.i, 0);
+    assert_eq!(cpu.dt, 0);
+    assert_eq!(cpu.keys, [false; KEYS]);
+    assert!(!cpu.is_hi_res());
+    assert!(cpu.memory.iter().all(|&byte| byte == 0));
+
+    // Settings survive
+    assert_eq!(cpu.variant(), Variant::Chip8);
+    assert_eq!(cpu.speed(), 1000);
+}
+
+#[test]
+fn test_warm_reset() {
+    let mut cpu = Chip8::new();
+    assert!(cpu.warm_reset().is_err());
+
+    cpu.load_rom(&[0x60, 0x2A, 0xF0, 0x55], None).unwrap();
+    cpu.tick().unwrap();
+    cpu.i = 0x200;
+    cpu.tick().unwrap();
+    assert_eq!(cpu.memory[0x200], 0x2A);
+
+    cpu.warm_reset().unwrap();
+    assert_eq!(cpu.pc, ENTRY_POINT as u16);
+    assert_eq!(cpu.v[0], 0);
+    assert_eq!(cpu.memory[0x200..0x204], [0x60, 0x2A, 0xF0, 0x55]);
+    assert_eq!(cpu.memory[..SMALL_FONT_LEN], cpu.font().small);
 
-    assert_eq!(cpu.pc, 100);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0], 0x2A);
 }
 
 #[test]
-fn test_rnd_vx_byte() {
+fn test_rnd_default_seed() {
+    let rolls = || {
+        let mut cpu = Chip8::new();
+        cpu.load_rom(&[0xC0, 0xFF, 0x12, 0x00], None).unwrap();
+
+        (0..8)
+            .map(|_| {
+                cpu.tick().unwrap();
+                cpu.tick().unwrap();
+                cpu.v[0]
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let first = rolls();
+    assert_eq!(first, rolls());
+    // Not stuck on a constant like before
+    assert!(first.iter().any(|&byte| byte != first[0]));
+}
+
+#[test]
+fn test_rnd_scripted() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_random_source(Box::new(ScriptedRandom::new(vec![0xAB, 0xFF])));
+
+    test_opcode!(cpu, 0xC00F, ENTRY_POINT);
+    assert_eq!(cpu.v[0], 0x0B);
+    test_opcode!(cpu, 0xC1F0, ENTRY_POINT + 2);
+    assert_eq!(cpu.v[1], 0xF0);
+    test_opcode!(cpu, 0xC2FF, ENTRY_POINT + 4);
+    assert_eq!(cpu.v[2], 0xAB);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+#[should_panic(expected = "at least one byte")]
+fn test_rnd_scripted_empty() {
+    let _ = ScriptedRandom::new(vec![]);
 }
 
 #[test]
-fn test_drw_vx_vy_nibble() {
+fn test_rnd_vip() {
+    // Page 1 holds its own offsets, so each CXNN adds the new low byte of R9 to the high one
+    let interpreter: Vec<u8> = (0..0x200).map(|i: u16| i.to_le_bytes()[0]).collect();
+    let random = VipStyleRandom::from_interpreter(&interpreter, 0x0500).unwrap();
+
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_random_source(Box::new(random));
+    cpu.load_rom(&[0xC0, 0xFF, 0x61, 0x00, 0xC2, 0xFF], None).unwrap();
+    for _ in 0..3 {
+        cpu.tick().unwrap();
+    }
+
+    // R9 is 0x0501 after the first fetch, CXNN makes it 0x0702, two fetches make it 0x0704
+    assert_eq!(cpu.v[0], 0x07);
+    assert_eq!(cpu.v[2], 0x0C);
+    assert!(VipStyleRandom::from_interpreter(&interpreter[..0x1FF], 0).is_none());
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_rnd_save_state() {
+    let mut cpu = Chip8::new();
+    cpu.set_random_seed(42);
+    cpu.load_rom(&[0xC0, 0xFF, 0x12, 0x00], None).unwrap();
+    cpu.tick().unwrap();
+
+    let mut saved = cpu.clone();
+    for cpu in [&mut cpu, &mut saved] {
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+    }
+    assert_eq!(cpu.v[0], saved.v[0]);
 }
 
 #[test]
-fn test_skp_vx() {
+fn test_megachip_ld_hi() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::MegaChip);
+    cpu.load_rom(&[0x01, 0x12, 0x34, 0x56, 0xF0, 0x1E], None).unwrap();
+    cpu.v[0] = 0xFF;
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.i, 0x12_3456);
+    assert_eq!(cpu.pc, 0x204);
 
-    assert_eq!(cpu.pc, 100);
+    // I keeps all 24 bits
+    cpu.tick().unwrap();
+    assert_eq!(cpu.i, 0x12_3555);
 }
 
 #[test]
-fn test_sknp_vx() {
+fn test_megachip_opcodes_only_on_megachip() {
+    let cpu = Chip8::new();
+    assert_eq!(cpu.decode(0x0112), Instruction::Unknown(0x0112));
+    assert_eq!(cpu.decode(0x00B2), Instruction::Unknown(0x00B2));
+
+    let mut cpu = Chip8::new();
+    cpu.set_variant(Variant::MegaChip);
+    assert_eq!(cpu.decode(0x0112), Instruction::LdHi(0x12));
+    assert_eq!(cpu.decode(0x00B2), Instruction::Scu(2));
+    assert_eq!(cpu.decode(0x00E0), Instruction::Cls);
+}
+
+#[test]
+fn test_megachip_sprite() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::MegaChip);
+    let rom = [
+        0x00, 0x11, // Mega-Chip mode on
+        0xA2, 0x20, // Palette
+        0x02, 0x02, // Two colors
+        0x03, 0x02, // Sprites 2 wide
+        0x04, 0x01, // and 1 high
+        0x09, 0x01, // Collide with color 1
+        0xA2, 0x28, // Sprite
+        0xD0, 0x00, // Draw at (0, 0)
+        0xD0, 0x00, // Draw again over itself
+        0x00, 0xE0, // Show the frame
+    ];
+    let mut program = rom.to_vec();
+    program.resize(0x20, 0);
+    program.extend([0xFF, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x01, 0x02]);
+    cpu.load_rom(&program, None).unwrap();
+
+    for _ in 0..8 {
+        cpu.tick().unwrap();
+    }
+    let mega = cpu.mega_screen().unwrap();
+    assert_eq!(mega.index(0, 0), 1);
+    assert_eq!(mega.index(1, 0), 2);
+    assert_eq!(cpu.v[0xF], 0);
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0xF], 1);
+
+    // Nothing shows until 00E0
+    assert_eq!(cpu.mega_screen().unwrap().pixel(0, 0), 0);
+    cpu.tick().unwrap();
+    let mega = cpu.mega_screen().unwrap();
+    assert_eq!(mega.pixel(0, 0), 0xFFFF_0000);
+    assert_eq!(mega.pixel(1, 0), 0xFF00_FF00);
+    assert_eq!(mega.index(0, 0), 0);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_megachip_blend() {
+    let mut mega = MegaScreen::new();
+    mega.load_palette(&[0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x00, 0x00, 0xFF]);
+    mega.sprite_width = 1;
+    mega.sprite_height = 1;
+
+    mega.blend = BlendMode::Half;
+    mega.draw_sprite(0, 0, |_| 1);
+    mega.present();
+    assert_eq!(mega.pixel(0, 0), 0xFF80_8080);
+
+    // Half transparent blue over black, in normal mode
+    mega.blend = BlendMode::Normal;
+    mega.draw_sprite(1, 0, |_| 2);
+    mega.present();
+    assert_eq!(mega.pixel(1, 0), 0xFF00_0080);
+
+    // No collision color until 09NN, not even the empty background
+    assert!(!mega.draw_sprite(2, 0, |_| 1));
+    mega.collision_color = Some(0);
+    assert!(mega.draw_sprite(3, 0, |_| 1));
+
+    // Clipped at the bottom
+    mega.sprite_height = 2;
+    mega.draw_sprite(0, MEGA_HEIGHT - 1, |_| 1);
+    assert_eq!(mega.index(0, MEGA_HEIGHT - 1), 1);
 }
 
 #[test]
-fn test_ld_vx_dt() {
+fn test_megachip_sample() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::MegaChip);
+    let mut program = vec![0x00, 0x11, 0xA2, 0x08, 0x06, 0x01, 0x07, 0x00];
+    program.extend([0x1F, 0x40, 0x00, 0x00, 0x04, 0x00, 0x80, 0x90, 0xA0, 0xB0]);
+    cpu.load_rom(&program, None).unwrap();
+
+    for _ in 0..3 {
+        cpu.tick().unwrap();
+    }
+    let (sample, data) = cpu.sample().unwrap();
+    assert_eq!(sample.rate, 8000);
+    assert!(!sample.looping);
+    assert_eq!(data, [0x80, 0x90, 0xA0, 0xB0]);
+
+    cpu.tick().unwrap();
+    assert!(cpu.sample().is_none());
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_megachip_pcm16() {
+    assert_eq!(to_pcm16(&[0x80, 0x00, 0xFF]), [0x00, 0x00, 0x00, 0x80, 0x00, 0x7F]);
 }
 
 #[test]
-fn test_ld_vx_k() {
+fn test_megachip_big_rom() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    assert!(cpu.load_rom(&vec![0; 0x20000], None).is_err());
+
+    cpu.set_variant(Variant::MegaChip);
+    let mut rom = vec![0; 0x20000];
+    rom[0x1FFFF] = 0xAB;
+    cpu.load_rom(&rom, None).unwrap();
 
-    assert_eq!(cpu.pc, 100);
+    assert_eq!(cpu.memory.len(), 0x40000);
+    assert_eq!(cpu.read(ENTRY_POINT + 0x1FFFF), 0xAB);
+
+    // Only as much cache as PC can reach
+    cpu.set_instruction_cache(true);
+    assert_eq!(cpu.instruction_cache.as_ref().unwrap().len(), 0x10000);
+    cpu.load_rom(&vec![0; 0x80000], None).unwrap();
+    assert_eq!(cpu.instruction_cache.as_ref().unwrap().len(), 0x10000);
+}
+
+#[test]
+fn test_renderer_dither() {
+    let mut renderer = Renderer::new();
+    let mut mega = MegaScreen::new();
+    renderer.set_layout(MEGA_WIDTH, MEGA_HEIGHT, 1, LCD_WIDTH);
+
+    // Black everywhere, then white and 50% gray rows
+    mega.load_palette(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x80, 0x80]);
+    mega.sprite_width = MEGA_WIDTH;
+    mega.sprite_height = 1;
+    mega.draw_sprite(0, 1, |_| 1);
+    mega.draw_sprite(0, 2, |_| 2);
+    mega.present();
+
+    // 72 pixels of padding on each side
+    let row = renderer.dither_row(&mega, 0);
+    assert!(row[9..41].iter().all(|&byte| byte == 0));
+
+    let row = renderer.dither_row(&mega, 1);
+    assert!(row[9..41].iter().all(|&byte| byte == 0xFF));
+
+    let row = renderer.dither_row(&mega, 2);
+    let lit: u32 = row[9..41].iter().map(|byte| byte.count_ones()).sum();
+    assert_eq!(lit, 128);
+
+    let mut frame = vec![0; LCD_ROW_BYTES * LCD_HEIGHT];
+    let mut marked = vec![];
+    renderer.render_mega(&mut mega, &mut frame, |start, end| marked.push((start, end)));
+    assert_eq!(marked, [(24, 215)]);
+
+    // Nothing new shown, nothing drawn
+    renderer.render_mega(&mut mega, &mut frame, |start, end| marked.push((start, end)));
+    assert_eq!(marked.len(), 1);
+}
+
+#[test]
+fn test_hi_res_detect() {
+    assert_eq!(Variant::Chip8.detect(&[0x12, 0x60, 0x00]), Variant::Chip8HiRes);
+    assert_eq!(Variant::Chip8.detect(&[0x12, 0x40, 0x00]), Variant::Chip8);
+    assert_eq!(Variant::SuperChip.detect(&[0x12, 0x60]), Variant::SuperChip);
+}
+
+#[test]
+fn test_hi_res_entry_point() {
+    let mut cpu = Chip8::new();
+    cpu.set_variant(Variant::Chip8HiRes);
+    let mut rom = vec![0x12, 0x60];
+    rom.resize(0xC0, 0);
+    // Sprite at the bottom of the 64x64 screen, then clear it
+    rom.extend([0x60, 0x3F, 0xA0, 0x00, 0xD0, 0x01, 0x02, 0x30]);
+    cpu.load_rom(&rom, None).unwrap();
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x2C0);
+    assert_eq!((cpu.width(), cpu.height()), (64, 64));
+
+    for _ in 0..3 {
+        cpu.tick().unwrap();
+    }
+    assert!(cpu.gfx_buffer.get(63, 63));
+
+    cpu.tick().unwrap();
+    assert!(!cpu.gfx_buffer.get(63, 63));
 }
 
 #[test]
-fn test_ld_dt_vx() {
+fn test_hi_res_jump_elsewhere() {
+    // 1260 anywhere else, or on other variants, is a plain jump
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::Chip8);
+    cpu.load_rom(&[0x12, 0x60], None).unwrap();
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x260);
+    assert_eq!((cpu.width(), cpu.height()), (64, 32));
+}
+
+#[test]
+fn test_renderer_hi_res_layout() {
+    let mut renderer = Renderer::new();
+    let mut framebuffer = Framebuffer::new(64, 64);
+    let mut frame = vec![0; LCD_ROW_BYTES * LCD_HEIGHT];
+    let mut marked = vec![];
+
+    renderer.set_layout(64, 64, 3, LCD_WIDTH);
+    renderer.render(&mut framebuffer, &mut frame, |start, end| {
+        marked.push((start, end));
+    });
+    assert_eq!(marked, [(24, 215)]);
+
+    // 104 pixels of padding on each side
+    let line = &frame[24 * LCD_ROW_BYTES..25 * LCD_ROW_BYTES];
+    assert_eq!(line[12], 0);
+    assert_eq!(line[13], 0xFF);
+    assert_eq!(line[36], 0xFF);
+    assert_eq!(line[37], 0);
+}
+
+#[test]
+fn test_chip8x_entry_point() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    cpu.load_rom(&[0x60, 0x12], None).unwrap();
+    assert_eq!(cpu.pc, 0x300);
+    assert_eq!(cpu.memory[0x300], 0x60);
+    assert_eq!(cpu.max_rom_size(), 0xBA0);
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0], 0x12);
+}
+
+#[test]
+fn test_chip8x_background() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    cpu.load_rom(&[0x02, 0xA0, 0x02, 0xA0], None).unwrap();
+    assert_eq!(cpu.color_map.background(), 2);
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.color_map.background(), 0);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.color_map.background(), 4);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_chip8x_color_zones() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    // Zones 1-2 across and 3 down in green, then 3 rows of the zone at (40, 5) in white
+    cpu.load_rom(&[0xB0, 0x20, 0xB3, 0x53], None).unwrap();
+    cpu.v[0] = 0x11;
+    cpu.v[1] = 0x04;
+    cpu.v[2] = 0x03;
+    cpu.tick().unwrap();
+
+    assert_eq!(cpu.color_map.foreground(8, 12), 4);
+    assert_eq!(cpu.color_map.foreground(23, 15), 4);
+    assert_eq!(cpu.color_map.foreground(24, 12), 1);
+    assert_eq!(cpu.color_map.foreground(8, 16), 1);
+
+    cpu.v[3] = 40;
+    cpu.v[4] = 7;
+    cpu.v[5] = 5;
+    cpu.tick().unwrap();
+    assert_eq!(cpu.color_map.foreground(40, 4), 1);
+    assert_eq!(cpu.color_map.foreground(47, 5), 7);
+    assert_eq!(cpu.color_map.foreground(40, 7), 7);
+    assert_eq!(cpu.color_map.foreground(40, 8), 1);
+}
+
+#[test]
+fn test_chip8x_second_keypad() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    cpu.load_rom(&[0xE0, 0xF2, 0x00, 0x00, 0xE0, 0xF5], None).unwrap();
+    cpu.v[0] = 0x5;
+    cpu.keys[0x5] = true;
+
+    // Keypad 1 doesn't count
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x302);
+
+    cpu.second_keys[0x5] = true;
+    cpu.pc = 0x300;
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x304);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x306);
+}
+
+#[test]
+fn test_chip8x_add_colors() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    cpu.load_rom(&[0x50, 0x11], None).unwrap();
+    cpu.v[0] = 0x35;
+    cpu.v[1] = 0x76;
+
+    // 3 + 7 and 5 + 6 wrap at 8 on their own
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0], 0x23);
+    assert_eq!(cpu.pc, 0x302);
+
+    let cpu = Chip8::new();
+    assert_eq!(cpu.decode(0x5011), Instruction::SeVxVy(0, 1));
+}
+
+#[test]
+fn test_chip8x_port() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    cpu.load_rom(&[0xF0, 0xF8, 0xF1, 0xFB], None).unwrap();
+    cpu.v[0] = 0x42;
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.port_output(), 0x42);
+
+    // Waits for input
+    cpu.tick().unwrap();
+    assert_eq!(cpu.pc, 0x302);
+
+    cpu.set_port_input(0x99);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[1], 0x99);
+    assert_eq!(cpu.pc, 0x304);
 }
 
 #[test]
-fn test_ld_st_vx() {
+fn test_chip8x_opcodes_only_on_chip8x() {
+    let cpu = Chip8::new();
+    assert_eq!(cpu.decode(0xB123), Instruction::JpV0Addr(0x123));
+
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8X));
+    cpu.set_variant(Variant::Chip8X);
+    assert_eq!(cpu.decode(0xB123), Instruction::ColorRows(1, 2, 3));
+}
+
+#[test]
+fn test_renderer_colors() {
+    let mut renderer = Renderer::new();
+    let mut framebuffer = Framebuffer::new(64, 32);
+    let mut colors = ColorMap::new();
+    colors.fill(0, 0, 8, 32, 7);
+    renderer.set_layout(64, 32, 6, LCD_WIDTH);
+
+    // White lit pixel, then the blue background lit every 4 pixels on this line
+    let row = renderer.dither_colors(1 << (FRAMEBUFFER_WIDTH - 1), &colors, 0, 24);
+    assert_eq!(row[1], 0b1111_1100);
+    assert_eq!(row[2], 0b1000_1000);
+
+    let mut frame = vec![0; LCD_ROW_BYTES * LCD_HEIGHT];
+    let mut marked = vec![];
+    framebuffer.take_dirty_rows();
+    framebuffer.set(0, 1, true);
+    renderer.render_colors(&mut framebuffer, &colors, &mut frame, |start, end| {
+        marked.push((start, end));
+    });
+    assert_eq!(marked, [(24, 215)]);
+
+    // Blue lights 2 in 16 pixels of the background
+    let lit: u32 = frame[..]
+        .chunks(LCD_ROW_BYTES)
+        .skip(24)
+        .take(4)
+        .map(|line| line[9..48].iter().map(|byte| byte.count_ones()).sum::<u32>())
+        .sum();
+    assert_eq!(lit, 39 * 8 * 4 * 2 / 16);
+}
+
+#[test]
+fn test_font_sets() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_font(Font::new(FontSet::Vip)).unwrap();
+    cpu.load_rom(&[0xF0, 0x29], None).unwrap();
+    cpu.v[0] = 0x7;
+    cpu.tick().unwrap();
+
+    assert_eq!(cpu.i, 35);
+    assert_eq!(cpu.memory[35..40], [0xF0, 0x10, 0x10, 0x10, 0x10]);
+
+    // Only the low nibble picks the glyph
+    assert_eq!(cpu.font().small_glyph(0x17), 35);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_font_addresses() {
+    let mut cpu = Chip8::new();
+    let font = Font::new(FontSet::Schip).at(0x100, 0x150);
+    cpu.set_font(font.clone()).unwrap();
+    cpu.load_rom(&[0xF0, 0x29, 0xF0, 0x30], None).unwrap();
+    cpu.v[0] = 0xA;
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.i, 0x100 + 50);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.i, 0x150 + 100);
+
+    // Super-Chip has no big letters, Octo's are used
+    assert_eq!(cpu.memory[0x150 + 100..0x150 + 110], Font::new(FontSet::Octo).big[100..110]);
+    assert_eq!(cpu.memory[0x100..0x100 + SMALL_FONT_LEN], font.small);
+
+    // Kept across resets
+    cpu.warm_reset().unwrap();
+    assert_eq!(cpu.font(), &font);
+    assert_eq!(cpu.memory[0x150..0x150 + BIG_FONT_LEN], font.big);
 }
 
 #[test]
-fn test_add_i_vx() {
+fn test_font_custom() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    let font = Font::custom([0x11; SMALL_FONT_LEN], [0x22; BIG_FONT_LEN]);
+    cpu.set_font(font).unwrap();
 
-    assert_eq!(cpu.pc, 100);
+    assert_eq!(cpu.memory[0x00], 0x11);
+    assert_eq!(cpu.memory[0x50], 0x22);
 }
 
 #[test]
-fn test_ld_f_vx() {
+fn test_font_check() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.set_variant(Variant::Chip8);
+
+    let past_end = Font::default().at(0x000, 0xFA0);
+    assert!(cpu.set_font(past_end).is_err());
+
+    let overlapping = Font::default().at(0x000, 0x040);
+    assert!(cpu.set_font(overlapping).is_err());
+
+    assert_eq!(cpu.font(), &Font::default());
+}
+
+#[test]
+fn test_memory_map() {
+    let cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8));
+    assert_eq!(cpu.memory.len(), 0x1000);
+    assert!(cpu.instruction_cache.is_none());
+
+    let mut cpu = Chip8::with_memory_map(MemoryMap::ETI_660);
+    cpu.set_variant(Variant::Chip8);
+    cpu.load_rom(&[0x60, 0x12], None).unwrap();
+    assert_eq!(cpu.pc, 0x600);
+    assert_eq!(cpu.max_rom_size(), 0xA00);
+
+    cpu.tick().unwrap();
+    assert_eq!(cpu.v[0], 0x12);
+
+    // Kept across resets
+    cpu.warm_reset().unwrap();
+    assert_eq!(cpu.pc, 0x600);
+    assert_eq!(cpu.memory_map(), &MemoryMap::ETI_660);
+}
+
+#[test]
+fn test_memory_map_reserved() {
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::Chip8));
+    cpu.set_variant(Variant::Chip8);
+    assert_eq!(cpu.max_rom_size(), 0xEA0 - 0x200);
+
+    assert!(cpu.load_rom(&vec![0; 0xEA0 - 0x200], None).is_ok());
+    assert!(cpu.load_rom(&vec![0; 0xEA0 - 0x1FF], None).is_err());
+
+    // Below the entry point doesn't limit the program
+    let map = MemoryMap::new(0x200, 0x1000).reserved(0x000..0x200);
+    let mut cpu = Chip8::with_memory_map(map);
+    cpu.set_variant(Variant::SuperChip);
+    assert_eq!(cpu.max_rom_size(), 0xE00);
+}
+
+/// Runs schip-test as Super-Chip until it shows which interpreter it detected
+fn schip_test_detects(quirks: Quirks) -> u16 {
+    let info = ROMS.iter().find(|info| info.name == "schip-test").unwrap();
+    let mut cpu = Chip8::with_memory_map(MemoryMap::for_variant(Variant::SuperChip));
+    cpu.set_quirks(quirks);
+    cpu.load_rom(info.rom, None).unwrap();
+    cpu.set_speed(info.speed);
+
+    // Super-Chip waits for a key before its scroll tests
+    for frame in 0..60 {
+        cpu.keys[0] = frame == 30;
+        cpu.run_for(16_667).unwrap();
+    }
+
+    // The message is an LD I instruction pointing at the text
+    u16::from_be_bytes([cpu.memory[0x42C], cpu.memory[0x42D]])
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_schip_test_detection() {
+    let quirks = Quirks::for_variant(Variant::SuperChip);
+    assert_eq!(schip_test_detects(quirks), 0xA4C5); // SCHIP 1.1
+
+    // Only DXY0 in lo-res tells it apart from CHIP-48
+    let quirks = Quirks {
+        big_sprite: BigSprite::Empty,
+        ..quirks
+    };
+    assert_eq!(schip_test_detects(quirks), 0xA499); // CHIP-48
 }
 
 #[test]
-fn test_ld_b_vx() {
+fn test_big_sprite_lo_res() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.i = 0x300;
+    cpu.memory[0x300..0x320].fill(0xFF);
+
+    // 16 rows of 8 pixels on Super-Chip
+    test_opcode!(cpu, 0xD000, ENTRY_POINT);
+    assert_eq!(cpu.gfx_buffer.row(15).count_ones(), 8);
+    assert_eq!(cpu.gfx_buffer.row(16), 0);
+
+    // Nothing on the VIP
+    cpu.set_variant(Variant::Chip8);
+    cpu.gfx_buffer.clear();
+    test_opcode!(cpu, 0xD000, ENTRY_POINT + 2);
+    assert!(cpu.gfx_buffer.rows().iter().all(|&row| row == 0));
+    assert_eq!(cpu.v[0x0F], 0);
+
+    cpu.set_variant(Variant::XoChip);
+    test_opcode!(cpu, 0xD000, ENTRY_POINT + 4);
+    assert_eq!(cpu.gfx_buffer.row(15).count_ones(), 16);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_sprite_clipping() {
+    let mut cpu = Chip8::new();
+    cpu.i = 0x300;
+    cpu.memory[0x300..0x304].fill(0xFF);
+    cpu.v[0] = 60;
+    cpu.v[1] = 30;
+
+    test_opcode!(cpu, 0xD014, ENTRY_POINT);
+    assert_eq!(cpu.gfx_buffer.row(30).count_ones(), 4);
+    assert_eq!(cpu.gfx_buffer.row(0), 0);
+    assert_eq!(cpu.gfx_buffer.row(1), 0);
+
+    // The start position still wraps
+    cpu.gfx_buffer.clear();
+    cpu.v[0] = 64 + 4;
+    cpu.v[1] = 32 + 2;
+    test_opcode!(cpu, 0xD011, ENTRY_POINT + 2);
+    assert!(cpu.gfx_buffer.get(4, 2));
+
+    cpu.set_variant(Variant::XoChip);
+    cpu.gfx_buffer.clear();
+    cpu.v[0] = 60;
+    cpu.v[1] = 30;
+    test_opcode!(cpu, 0xD014, ENTRY_POINT + 4);
+    assert_eq!(cpu.gfx_buffer.row(30).count_ones(), 8);
+    assert_eq!(cpu.gfx_buffer.row(1).count_ones(), 8);
 }
 
 #[test]
-fn test_ld_i_vx() {
+fn test_collision_rows() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.i = 0x300;
+    cpu.memory[0x300..0x320].fill(0xFF);
+    cpu.memory[0x300..0x302].fill(0x00);
+    cpu.hi_res = true;
+    cpu.gfx_buffer.resize(128, 64);
+
+    test_opcode!(cpu, 0xD000, ENTRY_POINT);
+    assert_eq!(cpu.v[0x0F], 0);
+
+    // 15 rows collide, the empty first one doesn't
+    test_opcode!(cpu, 0xD000, ENTRY_POINT + 2);
+    assert_eq!(cpu.v[0x0F], 15);
+
+    // 6 rows fall off the bottom and 2 of the rest collide
+    cpu.gfx_buffer.clear();
+    cpu.gfx_buffer.set(0, 60, true);
+    cpu.gfx_buffer.set(0, 61, true);
+    cpu.v[1] = 54;
+    test_opcode!(cpu, 0xD010, ENTRY_POINT + 4);
+    assert_eq!(cpu.v[0x0F], 8);
+
+    // Lo-res only flags that something collided
+    cpu.hi_res = false;
+    cpu.gfx_buffer.resize(64, 32);
+    cpu.v[1] = 0;
+    cpu.i = 0x302;
+    test_opcode!(cpu, 0xD012, ENTRY_POINT + 6);
+    test_opcode!(cpu, 0xD012, ENTRY_POINT + 8);
+    assert_eq!(cpu.v[0x0F], 1);
+
+    cpu.set_variant(Variant::XoChip);
+    cpu.hi_res = true;
+    cpu.gfx_buffer.resize(128, 64);
+    test_opcode!(cpu, 0xD000, ENTRY_POINT + 10);
+    test_opcode!(cpu, 0xD000, ENTRY_POINT + 12);
+    assert_eq!(cpu.v[0x0F], 1);
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_analyzer_code_and_data() {
+    let rom = [
+        0x22, 0x0C, // CALL 20C
+        0x30, 0x00, // SE V0, 0
+        0xF0, 0x00, 0x02, 0x12, // LD I, 212
+        0x12, 0x08, // JP 208
+        0xAB, 0xCD, // data
+        0x00, 0xFF, // HIGH
+        0x00, 0xEE, // RET
+        0xFF, 0xFF, 0xFF, 0xFF, // data
+    ];
+    let analysis = analyze(&rom, ENTRY_POINT);
+
+    // The skip jumps over all of F000 NNNN
+    let addresses: Vec<usize> = analysis
+        .instructions()
+        .map(|instruction| instruction.address)
+        .collect();
+    assert_eq!(addresses, [0x200, 0x202, 0x204, 0x208, 0x20C, 0x20E]);
+    assert!(analysis.is_code(0x206));
+    assert!(!analysis.is_code(0x20A));
+    assert_eq!(analysis.data(), [0x20A..0x20C, 0x210..0x214]);
+
+    assert_eq!(analysis.instruction(0x200).unwrap().flow, Flow::Call(0x20C));
+    assert_eq!(analysis.instruction(0x202).unwrap().flow, Flow::Skip);
+    assert_eq!(analysis.instruction(0x20E).unwrap().flow, Flow::Return);
+
+    assert!(analysis.uses(OpcodeFamily::SuperChip));
+    assert!(analysis.uses(OpcodeFamily::XoChip));
+    assert_eq!(analysis.recommended_variant(), Variant::XoChip);
+
+    let unsupported: Vec<u16> = analysis
+        .unsupported()
+        .map(|instruction| instruction.opcode)
+        .collect();
+    assert_eq!(unsupported, [0xF000]);
 }
 
 #[test]
-fn test_ld_vx_i() {
+fn test_analyzer_stops() {
+    let rom = [
+        0x00, 0x00, // written at run time
+        0xB3, 0x00, // JP V0, 300
+        0x00, 0xE0,
+    ];
+    let analysis = analyze(&rom, ENTRY_POINT);
+
+    assert_eq!(analysis.instructions().count(), 2);
+    assert_eq!(analysis.computed_jumps().count(), 1);
+    // Warned about, in case the ROM never writes over it
+    assert!(analysis.uses(OpcodeFamily::SelfModified));
+    assert_eq!(analysis.unsupported().count(), 1);
+    assert_eq!(analysis.recommended_variant(), Variant::Chip8);
+
+    // Machine code and unknown opcodes
+    let analysis = analyze(&[0x01, 0x23, 0xE0, 0x00], ENTRY_POINT);
+    assert_eq!(analysis.unsupported().count(), 2);
+    assert!(analysis.uses(OpcodeFamily::MachineCode));
+}
+
+#[test]
+fn test_analyzer_entry_point() {
+    let rom = [
+        0x13, 0x04, // JP 304
+        0xFF, 0xFF, // data
+        0x00, 0xE0, // CLS
+        0x13, 0x06, // JP 306
+    ];
+    let entry_point = MemoryMap::for_variant(Variant::Chip8X).entry_point;
+    let analysis = analyze(&rom, entry_point);
+
+    assert_eq!(analysis.entry_point(), 0x300);
+    assert_eq!(analysis.instructions().count(), 3);
+    let data = analysis.data();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0], 0x302..0x304);
+
+    let graph = ControlFlowGraph::new(&analysis);
+    let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+    assert_eq!(starts, [0x300, 0x304, 0x306]);
+    let dot = rom_to_dot(&rom, entry_point);
+    assert!(dot.contains("b300 [label=\"0x300  JP 0x304\\l\", penwidth=2];"));
+}
+
+#[test]
+fn test_analyzer_chip8x_colors() {
+    let rom = [
+        0x60, 0x11, // LD V0, 0x11
+        0xB0, 0x13, // COLOR V0, V1, 3
+        0x13, 0x02, // JP 302
+    ];
+    let entry_point = MemoryMap::for_variant(Variant::Chip8X).entry_point;
+    let analysis = analyze(&rom, entry_point);
+
+    // BXYN goes on to the next instruction, and is the only CHIP-8X opcode
+    assert_eq!(analysis.instructions().count(), 3);
+    assert_eq!(analysis.instruction(0x302).unwrap().flow, Flow::Next);
+    assert_eq!(analysis.computed_jumps().count(), 0);
+    assert!(analysis.uses(OpcodeFamily::Chip8X));
+    assert_eq!(analysis.recommended_variant(), Variant::Chip8X);
+    assert_eq!(
+        analysis.instruction(0x302).unwrap().to_string(),
+        "COLOR V0, V1, 3"
+    );
+
+    // From 0x200 it's a BNNN jump
+    let analysis = analyze(&rom, ENTRY_POINT);
+    assert_eq!(analysis.computed_jumps().count(), 1);
+    assert_eq!(analysis.recommended_variant(), Variant::Chip8);
+}
+
+#[test]
+fn test_analyzer_unknown_entry_point() {
+    // Jumps back to 0x302, which is out of the ROM from 0x200
+    let chip8x = [0x60, 0x11, 0x60, 0x22, 0x13, 0x02];
+    let analysis = analyze_unknown(&chip8x);
+    assert_eq!(analysis.entry_point(), 0x300);
+    assert_eq!(analysis.misfits(), 0);
+    assert_eq!(analyze(&chip8x, ENTRY_POINT).misfits(), 1);
+    assert_eq!(analysis.recommended_variant(), Variant::Chip8X);
+
+    // And this one to 0x202, which is before the ROM from 0x300
+    let chip8 = [0x60, 0x11, 0x60, 0x22, 0x12, 0x02];
+    let analysis = analyze_unknown(&chip8);
+    assert_eq!(analysis.entry_point(), ENTRY_POINT);
+    assert_eq!(analysis.recommended_variant(), Variant::Chip8);
+
+    // A tie goes to 0x300 for CHIP-8X opcodes only
+    assert_eq!(analyze_unknown(&[0xB0, 0x13]).entry_point(), 0x300);
+    assert_eq!(analyze_unknown(&[0x00, 0xE0]).entry_point(), ENTRY_POINT);
+
+    for info in ROMS {
+        assert_eq!(analyze_unknown(info.rom).entry_point(), ENTRY_POINT, "{}", info.name);
+    }
+}
+
+#[test]
+fn test_analyzer_variants() {
+    let bundled = |name| ROMS.iter().find(|info| info.name == name).unwrap().rom;
+
+    let variant = |name| analyze(bundled(name), ENTRY_POINT).recommended_variant();
+
+    assert_eq!(variant("maze"), Variant::Chip8);
+    assert_eq!(variant("octogon"), Variant::SuperChip);
+    assert_eq!(variant("superneatboy"), Variant::XoChip);
+
+    // Hi-res ROMs jump over the interpreter patch
+    let analysis = analyze(&[0x12, 0x60], ENTRY_POINT);
+    assert_eq!(analysis.instruction(0x200).unwrap().flow, Flow::Jump(0x2C0));
+
+    let mut rom = vec![0x12, 0xC0]; // JP 2C0
+    rom.resize(0xC0, 0);
+    rom.extend([0x00, 0x11, 0x01, 0x00, 0x12, 0x34, 0x00, 0xFD]); // MEGAON, LDHI 1234, EXIT
+    let analysis = analyze(&rom, ENTRY_POINT);
+    assert_eq!(analysis.recommended_variant(), Variant::MegaChip);
+    assert_eq!(analysis.instruction(0x2C2).unwrap().len, 4);
+    assert!(analysis.instruction(0x2C6).is_some());
+
+    // FX75 and FX85 aren't in yet
+    let unsupported = analyze(bundled("turnover77"), ENTRY_POINT).unsupported().count();
+    assert!(unsupported > 0);
+}
+
+#[test]
+fn test_disassembly() {
+    let rom = [0x22, 0x0A, 0xF0, 0x00, 0x02, 0x12, 0xB3, 0x00];
+    let decoder = Decoder::new(&rom, ENTRY_POINT, false);
+    let text = |address| decoder.decode(address).unwrap().to_string();
+
+    assert_eq!(text(0x200), "CALL 0x20A");
+    assert_eq!(text(0x202), "LD I, 0x0212");
+    assert_eq!(text(0x206), "JP V0, 0x300");
+}
+
+#[test]
+fn test_control_flow_graph() {
+    let rom = [
+        0x22, 0x0A, // CALL 20A
+        0x30, 0x00, // SE V0, 00
+        0x12, 0x08, // JP 208
+        0xB3, 0x00, // JP V0, 300
+        0x12, 0x08, // JP 208
+        0x60, 0x01, // LD V0, 01
+        0x00, 0xEE, // RET
+    ];
+    let graph = ControlFlowGraph::new(&analyze(&rom, ENTRY_POINT));
+
+    let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+    assert_eq!(starts, [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+    assert_eq!(graph.blocks[&0x20A].instructions.len(), 2);
+
+    let edge = |from, to, kind| Edge { from, to, kind };
+    for expected in [
+        edge(0x200, Some(0x20A), EdgeKind::Call),
+        edge(0x200, Some(0x202), EdgeKind::Next),
+        edge(0x202, Some(0x204), EdgeKind::Next),
+        edge(0x202, Some(0x206), EdgeKind::Skip),
+        edge(0x204, Some(0x208), EdgeKind::Jump),
+        edge(0x206, None, EdgeKind::Unresolved),
+        edge(0x208, Some(0x208), EdgeKind::Jump),
+        edge(0x20A, Some(0x202), EdgeKind::Return),
+    ] {
+        assert!(graph.edges.contains(&expected), "missing {expected:?}");
+    }
+    assert_eq!(graph.edges.len(), 8);
+
+    let dot = rom_to_dot(&rom, ENTRY_POINT);
+    assert!(dot.starts_with("digraph rom {"));
+    assert!(dot.contains("b200 -> b20A [label=\"call\", color=blue];"));
+    assert!(dot.contains("0x20C  RET\\l"));
+    assert!(dot.contains("u206 [label=\"?\", shape=diamond, color=red];"));
+}
+
+#[test]
+fn test_trace_line() {
     let mut cpu = Chip8::new();
-    cpu.tick();
+    cpu.load_rom(&[0x60, 0x12, 0x22, 0x06, 0x12, 0x04, 0x00, 0xEE], None).unwrap();
+    cpu.v[0xF] = 1;
+    cpu.dt = 0x3C;
+
+    let line = cpu.trace_entry().to_string();
+    assert_eq!(
+        line,
+        "PC:0200 OP:6012 I:0000 V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 \
+         V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:01 SP:00 DT:3C ST:00"
+    );
+}
 
-    assert_eq!(cpu.pc, 100);
+#[test]
+fn test_tracer() {
+    let program = [
+        0x60, 0x12, // LD V0, 12
+        0x22, 0x06, // CALL 206
+        0x12, 0x04, // JP 204
+        0x00, 0xEE, // RET
+    ];
+    let ring = Rc::new(RefCell::new(TraceRing::new(3)));
+    let writer = Rc::new(RefCell::new(TraceWriter::new(String::new())));
+
+    let mut cpu = Chip8::new();
+    cpu.set_idle_skip(false);
+    cpu.load_rom(&program, None).unwrap();
+    cpu.set_tracer(Some(writer.clone()));
+    for _ in 0..4 {
+        cpu.tick().unwrap();
+    }
+
+    let writer = writer.borrow();
+    let lines: Vec<&str> = writer.get_ref().lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("PC:0200 OP:6012 I:0000 V0:00 "));
+    assert!(lines[1].starts_with("PC:0202 OP:2206 I:0000 V0:12 "));
+    assert!(lines[2].ends_with("SP:01 DT:00 ST:00"));
+    assert!(lines[3].starts_with("PC:0204 OP:1204 "));
+    assert!(writer.result().is_ok());
+
+    // Only the newest ones are kept
+    cpu.set_tracer(Some(ring.clone()));
+    for _ in 0..5 {
+        cpu.tick().unwrap();
+    }
+    let ring = ring.borrow();
+    assert_eq!(ring.entries().count(), 3);
+    assert!(ring.entries().all(|entry| entry.pc == 0x204));
+    assert_eq!(ring.last().unwrap().v[0], 0x12);
 }
-*/
@@ -0,0 +1,133 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiltDirection {
+    Left,
+    Right,
+    /// Top of the screen away from the player
+    Up,
+    /// Top of the screen toward the player
+    Down,
+}
+
+/// Maps accelerometer tilt to CHIP-8 keys
+///
+/// Readings are taken relative to the calibrated rest position. A direction turns on past the
+/// threshold and only turns off once the tilt drops the hysteresis below it, so holding the
+/// Playdate near the threshold doesn't make keys flicker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tilt {
+    // One bit per CHIP-8 key, indexed by `TiltDirection`
+    bindings: [u16; 4],
+    /// Tilt in g needed to press a key
+    pub threshold: f32,
+    /// How far in g the tilt has to drop below the threshold to release the key
+    pub hysteresis: f32,
+    rest: Option<(f32, f32)>,
+    // -1, 0 or 1 for each axis
+    state: [i8; 2],
+}
+
+impl Tilt {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            bindings: [0; 4],
+            threshold: 0.25,
+            hysteresis: 0.1,
+            rest: None,
+            state: [0; 2],
+        }
+    }
+
+    #[must_use]
+    pub const fn with(mut self, direction: TiltDirection, key: u8) -> Self {
+        self.bindings[direction as usize] |= 1 << (key & 0xF);
+        self
+    }
+
+    /// Binds tilting left and right to a key pair
+    #[must_use]
+    pub const fn with_x(self, left: u8, right: u8) -> Self {
+        self.with(TiltDirection::Left, left)
+            .with(TiltDirection::Right, right)
+    }
+
+    /// Binds tilting up and down to a key pair
+    #[must_use]
+    pub const fn with_y(self, up: u8, down: u8) -> Self {
+        self.with(TiltDirection::Up, up)
+            .with(TiltDirection::Down, down)
+    }
+
+    #[must_use]
+    pub const fn with_threshold(mut self, threshold: f32, hysteresis: f32) -> Self {
+        self.threshold = threshold;
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Uses the next reading as the rest position
+    pub fn calibrate(&mut self) {
+        self.rest = None;
+        self.state = [0; 2];
+    }
+
+    #[must_use]
+    pub const fn is_active(&self, direction: TiltDirection) -> bool {
+        match direction {
+            TiltDirection::Left => self.state[0] < 0,
+            TiltDirection::Right => self.state[0] > 0,
+            TiltDirection::Up => self.state[1] < 0,
+            TiltDirection::Down => self.state[1] > 0,
+        }
+    }
+
+    /// Takes an accelerometer reading in g
+    pub fn update(&mut self, x: f32, y: f32) {
+        let (rest_x, rest_y) = *self.rest.get_or_insert((x, y));
+
+        self.state[0] = self.axis(self.state[0], x - rest_x);
+        self.state[1] = self.axis(self.state[1], y - rest_y);
+    }
+
+    fn axis(&self, state: i8, offset: f32) -> i8 {
+        let release = self.threshold - self.hysteresis;
+
+        match state {
+            1 if offset > release => 1,
+            -1 if offset < -release => -1,
+            _ if offset > self.threshold => 1,
+            _ if offset < -self.threshold => -1,
+            _ => 0,
+        }
+    }
+
+    /// Presses the keys of the current tilt on top of the keys already pressed
+    pub fn apply(&mut self, x: f32, y: f32, keys: &mut [bool; KEYS]) {
+        self.update(x, y);
+
+        let directions = [
+            TiltDirection::Left,
+            TiltDirection::Right,
+            TiltDirection::Up,
+            TiltDirection::Down,
+        ];
+
+        for direction in directions {
+            if !self.is_active(direction) {
+                continue;
+            }
+
+            for (key, state) in keys.iter_mut().enumerate() {
+                *state |= self.bindings[direction as usize] & (1 << key) != 0;
+            }
+        }
+    }
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
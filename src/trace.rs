@@ -0,0 +1,140 @@
+use alloc::collections::VecDeque;
+use core::fmt;
+
+/// Machine state just before an instruction runs
+///
+/// Shows as one line, to diff against traces of other emulators:
+///
+/// ```text
+/// PC:0200 OP:00E0 I:0000 V0:00 V1:00 V2:00 ... VF:00 SP:00 DT:00 ST:00
+/// ```
+///
+/// Every number is uppercase hex with a fixed width, except I which grows past 4 digits on
+/// Mega-Chip. OP is the first two bytes at PC, even for 4-byte instructions, and SP is how many
+/// return addresses are on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub i: u32,
+    pub v: [u8; 16],
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PC:{:04X} OP:{:04X} I:{:04X}",
+            self.pc, self.opcode, self.i
+        )?;
+
+        for (x, value) in self.v.iter().enumerate() {
+            write!(f, " V{x:X}:{value:02X}")?;
+        }
+
+        write!(
+            f,
+            " SP:{:02X} DT:{:02X} ST:{:02X}",
+            self.sp, self.dt, self.st
+        )
+    }
+}
+
+/// Gets every instruction `Chip8` runs, see `Chip8::set_tracer`
+pub trait Tracer {
+    fn trace(&mut self, entry: &TraceEntry);
+}
+
+/// Keeps the last instructions in a fixed amount of memory, for the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRing {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl TraceRing {
+    /// Allocates all `capacity` entries up front
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    #[must_use]
+    pub fn last(&self) -> Option<&TraceEntry> {
+        self.entries.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Tracer for TraceRing {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(*entry);
+    }
+}
+
+/// Writes a line per instruction as it runs, for the desktop
+///
+/// Stops writing after the first error, which `result` keeps.
+#[derive(Debug)]
+pub struct TraceWriter<W: fmt::Write> {
+    out: W,
+    result: fmt::Result,
+}
+
+impl<W: fmt::Write> TraceWriter<W> {
+    pub const fn new(out: W) -> Self {
+        Self {
+            out,
+            result: Ok(()),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns the error of the first line that couldn't be written
+    pub const fn result(&self) -> fmt::Result {
+        self.result
+    }
+
+    pub const fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: fmt::Write> Tracer for TraceWriter<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.result.is_ok() {
+            self.result = writeln!(self.out, "{entry}");
+        }
+    }
+}